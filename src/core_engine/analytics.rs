@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::clock::Timestamp;
+
+// Execution benchmarks over a period of trading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Benchmarks {
+    // Volume weighted average price of the trades.
+    pub vwap: Option<Decimal>,
+    // Time weighted average of the mid-price, over the time both sides were displayed.
+    pub twap: Option<Decimal>,
+    // Traded value, the sum of price times quantity.
+    pub notional: Decimal,
+    pub volume: Decimal,
+    pub trade_count: u64,
+}
+
+// Trades and mid-price samples of one instrument, kept for `retention` milliseconds so that
+// benchmarks can be computed over rolling windows, plus the running TWAP of the session.
+#[derive(Debug, Clone)]
+pub struct TradeAnalytics {
+    // (Timestamp, Price, Quantity) oldest first.
+    trades: VecDeque<(Timestamp, Decimal, Decimal)>,
+    // (Timestamp, Mid-price from then on) oldest first, None while a side of the book is empty.
+    mid_prices: VecDeque<(Timestamp, Option<Decimal>)>,
+    retention: Timestamp,
+    // Sum of mid-price times milliseconds, and the milliseconds, since the session started.
+    session_weighted_mid: Decimal,
+    session_mid_duration: Decimal,
+    // Mid-price of the session since when.
+    session_mid: Option<(Timestamp, Decimal)>,
+    // The session TWAP does not move once the trading day is over.
+    session_open: bool,
+}
+
+impl TradeAnalytics {
+    pub fn new(retention: Timestamp) -> TradeAnalytics {
+        TradeAnalytics {
+            trades: VecDeque::new(),
+            mid_prices: VecDeque::new(),
+            retention,
+            session_weighted_mid: dec!(0),
+            session_mid_duration: dec!(0),
+            session_mid: None,
+            session_open: true,
+        }
+    }
+
+    pub fn set_retention(&mut self, retention: Timestamp) {
+        self.retention = retention;
+    }
+
+    pub fn record_trade(&mut self, timestamp: Timestamp, price: Decimal, quantity: Decimal) {
+        self.trades.push_back((timestamp, price, quantity));
+        self.prune(timestamp);
+    }
+
+    // Only changes of the mid-price are kept.
+    pub fn record_mid_price(&mut self, timestamp: Timestamp, mid_price: Option<Decimal>) {
+        if self.mid_prices.back().map(|(_, mid)| *mid) == Some(mid_price) {
+            return;
+        }
+        self.accrue_session_mid(timestamp);
+        if self.session_open {
+            self.session_mid = mid_price.map(|mid| (timestamp, mid));
+        }
+        self.mid_prices.push_back((timestamp, mid_price));
+        self.prune(timestamp);
+    }
+
+    // Restart the session TWAP, e.g. at the start of a new trading day.
+    pub fn start_session(&mut self, now: Timestamp) {
+        self.session_weighted_mid = dec!(0);
+        self.session_mid_duration = dec!(0);
+        self.session_mid = self
+            .mid_prices
+            .back()
+            .and_then(|(_, mid)| *mid)
+            .map(|mid| (now, mid));
+        self.session_open = true;
+    }
+
+    // Freeze the session TWAP at the end of the trading day.
+    pub fn end_session(&mut self, now: Timestamp) {
+        self.accrue_session_mid(now);
+        self.session_mid = None;
+        self.session_open = false;
+    }
+
+    fn accrue_session_mid(&mut self, now: Timestamp) {
+        if let Some((since, mid)) = self.session_mid {
+            let duration = Decimal::from(now.saturating_sub(since));
+            self.session_weighted_mid += mid * duration;
+            self.session_mid_duration += duration;
+        }
+    }
+
+    pub fn session_twap(&self, now: Timestamp) -> Option<Decimal> {
+        let (mut weighted_mid, mut duration) =
+            (self.session_weighted_mid, self.session_mid_duration);
+        if let Some((since, mid)) = self.session_mid {
+            let open_duration = Decimal::from(now.saturating_sub(since));
+            weighted_mid += mid * open_duration;
+            duration += open_duration;
+        }
+        if duration.is_zero() {
+            // Nothing but a mid-price which has just been set.
+            return self.session_mid.map(|(_, mid)| mid);
+        }
+        Some(weighted_mid / duration)
+    }
+
+    // Benchmarks over the last `window` milliseconds, at most over the retention period.
+    pub fn rolling(&self, now: Timestamp, window: Timestamp) -> Benchmarks {
+        let window_start = now.saturating_sub(window);
+        let mut benchmarks = Benchmarks::default();
+        for (_, price, quantity) in self
+            .trades
+            .iter()
+            .filter(|(timestamp, _, _)| *timestamp >= window_start)
+        {
+            benchmarks.notional += price * quantity;
+            benchmarks.volume += quantity;
+            benchmarks.trade_count += 1;
+        }
+        if !benchmarks.volume.is_zero() {
+            benchmarks.vwap = Some(benchmarks.notional / benchmarks.volume);
+        }
+
+        let (mut weighted_mid, mut duration) = (dec!(0), dec!(0));
+        let ends = self
+            .mid_prices
+            .iter()
+            .skip(1)
+            .map(|(timestamp, _)| *timestamp)
+            .chain(std::iter::once(now));
+        for ((start, mid), end) in self.mid_prices.iter().zip(ends) {
+            let start = (*start).max(window_start);
+            let end = end.min(now);
+            if let (Some(mid), true) = (mid, end > start) {
+                weighted_mid += mid * Decimal::from(end - start);
+                duration += Decimal::from(end - start);
+            }
+        }
+        if !duration.is_zero() {
+            benchmarks.twap = Some(weighted_mid / duration);
+        }
+        benchmarks
+    }
+
+    // The latest mid-price sample from before the retention period is still needed,
+    // it holds at the start of the period.
+    fn prune(&mut self, now: Timestamp) {
+        let oldest = now.saturating_sub(self.retention);
+        while self
+            .trades
+            .front()
+            .is_some_and(|(timestamp, _, _)| *timestamp < oldest)
+        {
+            self.trades.pop_front();
+        }
+        while self
+            .mid_prices
+            .get(1)
+            .is_some_and(|(timestamp, _)| *timestamp <= oldest)
+        {
+            self.mid_prices.pop_front();
+        }
+    }
+}
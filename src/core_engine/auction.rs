@@ -0,0 +1,114 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::order::BuyOrSell;
+
+// Indicative result of a call auction if it were uncrossed right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuctionIndication {
+    pub price: Decimal,
+    pub matched_quantity: Decimal,
+    // Quantity left unmatched at the equilibrium price, and on which side.
+    pub imbalance: Decimal,
+    pub imbalance_side: Option<BuyOrSell>,
+}
+
+// Everything the equilibrium price is computed from.
+pub struct AuctionBook {
+    // (Price, Quantity) of every limit order price level.
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+    pub market_buy_quantity: Decimal,
+    pub market_sell_quantity: Decimal,
+    // Usually the last traded or previous close price.
+    pub reference_price: Option<Decimal>,
+}
+
+impl AuctionBook {
+    fn indication_at(&self, price: Decimal) -> AuctionIndication {
+        let buy_quantity: Decimal = self.market_buy_quantity
+            + self
+                .bids
+                .iter()
+                .filter(|(bid_price, _)| *bid_price >= price)
+                .map(|(_, quantity)| *quantity)
+                .sum::<Decimal>();
+        let sell_quantity: Decimal = self.market_sell_quantity
+            + self
+                .asks
+                .iter()
+                .filter(|(ask_price, _)| *ask_price <= price)
+                .map(|(_, quantity)| *quantity)
+                .sum::<Decimal>();
+        let imbalance_side = if buy_quantity > sell_quantity {
+            Some(BuyOrSell::Buy)
+        } else if sell_quantity > buy_quantity {
+            Some(BuyOrSell::Sell)
+        } else {
+            None
+        };
+        AuctionIndication {
+            price,
+            matched_quantity: buy_quantity.min(sell_quantity),
+            imbalance: (buy_quantity - sell_quantity).abs(),
+            imbalance_side,
+        }
+    }
+
+    // The equilibrium price maximises the executed quantity. Ties are broken by the smallest
+    // imbalance, then by market pressure (highest price when every candidate has a buy
+    // surplus, lowest when every candidate has a sell surplus) and finally by the price
+    // closest to the reference price.
+    pub fn equilibrium(&self) -> Option<AuctionIndication> {
+        let mut candidate_prices: Vec<Decimal> = self
+            .bids
+            .iter()
+            .chain(self.asks.iter())
+            .map(|(price, _)| *price)
+            .chain(self.reference_price)
+            .collect();
+        candidate_prices.sort();
+        candidate_prices.dedup();
+
+        let candidates: Vec<AuctionIndication> = candidate_prices
+            .into_iter()
+            .map(|price| self.indication_at(price))
+            .filter(|indication| indication.matched_quantity > dec!(0))
+            .collect();
+        let max_quantity = candidates
+            .iter()
+            .map(|indication| indication.matched_quantity)
+            .max()?;
+        let candidates: Vec<AuctionIndication> = candidates
+            .into_iter()
+            .filter(|indication| indication.matched_quantity == max_quantity)
+            .collect();
+        let min_imbalance = candidates
+            .iter()
+            .map(|indication| indication.imbalance)
+            .min()?;
+        let candidates: Vec<AuctionIndication> = candidates
+            .into_iter()
+            .filter(|indication| indication.imbalance == min_imbalance)
+            .collect();
+
+        let all_buy_surplus = candidates
+            .iter()
+            .all(|indication| indication.imbalance_side == Some(BuyOrSell::Buy));
+        let all_sell_surplus = candidates
+            .iter()
+            .all(|indication| indication.imbalance_side == Some(BuyOrSell::Sell));
+        if all_buy_surplus {
+            return candidates.last().copied();
+        }
+        if all_sell_surplus {
+            return candidates.first().copied();
+        }
+        match self.reference_price {
+            Some(reference_price) => candidates
+                .into_iter()
+                .min_by_key(|indication| (indication.price - reference_price).abs()),
+            None => candidates.first().copied(),
+        }
+    }
+}
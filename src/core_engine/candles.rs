@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::clock::Timestamp;
+
+// Bars are aligned on UTC boundaries, daily bars start at midnight UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandleInterval {
+    OneSecond,
+    OneMinute,
+    FiveMinutes,
+    OneHour,
+    OneDay,
+}
+
+impl CandleInterval {
+    pub const ALL: [CandleInterval; 5] = [
+        CandleInterval::OneSecond,
+        CandleInterval::OneMinute,
+        CandleInterval::FiveMinutes,
+        CandleInterval::OneHour,
+        CandleInterval::OneDay,
+    ];
+
+    pub fn millis(&self) -> Timestamp {
+        match self {
+            CandleInterval::OneSecond => 1_000,
+            CandleInterval::OneMinute => 60_000,
+            CandleInterval::FiveMinutes => 5 * 60_000,
+            CandleInterval::OneHour => 60 * 60_000,
+            CandleInterval::OneDay => 24 * 60 * 60_000,
+        }
+    }
+
+    // Start of the bar `timestamp` falls into.
+    pub fn bar_start(&self, timestamp: Timestamp) -> Timestamp {
+        timestamp - timestamp % self.millis()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candle {
+    pub start: Timestamp,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    // 0 for the flat bars filling intervals without any trade.
+    pub trade_count: u64,
+}
+
+impl Candle {
+    // Flat bar at `price` without any volume.
+    pub fn flat(start: Timestamp, price: Decimal) -> Candle {
+        Candle {
+            start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: dec!(0),
+            trade_count: 0,
+        }
+    }
+
+    fn record_trade(&mut self, price: Decimal, quantity: Decimal) {
+        if self.trade_count == 0 {
+            *self = Candle::flat(self.start, price);
+        }
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += quantity;
+        self.trade_count += 1;
+    }
+}
+
+// Bars of one interval : the finished ones and the one currently forming.
+#[derive(Debug, Clone)]
+pub struct CandleSeries {
+    interval: CandleInterval,
+    history: Vec<Candle>,
+    current: Option<Candle>,
+    // Oldest bars are dropped beyond this many finished bars.
+    history_limit: usize,
+}
+
+impl CandleSeries {
+    pub fn new(interval: CandleInterval, history_limit: usize) -> CandleSeries {
+        CandleSeries {
+            interval,
+            history: Vec::new(),
+            current: None,
+            history_limit,
+        }
+    }
+
+    pub fn interval(&self) -> CandleInterval {
+        self.interval
+    }
+
+    // Finished bars, oldest first.
+    pub fn history(&self) -> &[Candle] {
+        &self.history
+    }
+
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    pub fn record_trade(&mut self, timestamp: Timestamp, price: Decimal, quantity: Decimal) {
+        self.roll(timestamp);
+        let start = self.interval.bar_start(timestamp);
+        self.current
+            .get_or_insert_with(|| Candle::flat(start, price))
+            .record_trade(price, quantity);
+    }
+
+    // Finish the current bar once `now` is past it. Intervals without trades get flat bars at
+    // the last close, the bar containing `now` included. Nothing happens before the first trade.
+    pub fn roll(&mut self, now: Timestamp) {
+        let current = match self.current {
+            Some(current) => current,
+            None => return,
+        };
+        let start = self.interval.bar_start(now);
+        if start <= current.start {
+            return;
+        }
+        self.history.push(current);
+        let millis = self.interval.millis();
+        let missing_bars = (start - current.start) / millis - 1;
+        // Only the bars which would survive the history limit are worth creating.
+        let skipped_bars = missing_bars.saturating_sub(self.history_limit as u64);
+        let mut gap_start = current.start + (skipped_bars + 1) * millis;
+        while gap_start < start {
+            self.history.push(Candle::flat(gap_start, current.close));
+            gap_start += millis;
+        }
+        if self.history.len() > self.history_limit {
+            let excess = self.history.len() - self.history_limit;
+            self.history.drain(..excess);
+        }
+        self.current = Some(Candle::flat(start, current.close));
+    }
+}
+
+// Candle series of one instrument for every interval.
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    series: HashMap<CandleInterval, CandleSeries>,
+}
+
+impl Default for CandleAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CandleAggregator {
+    pub fn new() -> CandleAggregator {
+        CandleAggregator::with_history_limit(10_000)
+    }
+
+    pub fn with_history_limit(history_limit: usize) -> CandleAggregator {
+        CandleAggregator {
+            series: CandleInterval::ALL
+                .iter()
+                .map(|interval| (*interval, CandleSeries::new(*interval, history_limit)))
+                .collect(),
+        }
+    }
+
+    pub fn series(&self, interval: CandleInterval) -> Option<&CandleSeries> {
+        self.series.get(&interval)
+    }
+
+    pub fn record_trade(&mut self, timestamp: Timestamp, price: Decimal, quantity: Decimal) {
+        for series in self.series.values_mut() {
+            series.record_trade(timestamp, price, quantity);
+        }
+    }
+
+    pub fn roll(&mut self, now: Timestamp) {
+        for series in self.series.values_mut() {
+            series.roll(now);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::clock::Timestamp;
+
+// Price limits of an instrument. Bands are fractions of their reference price, e.g. 0.10 for 10%.
+// Orders priced outside the band are rejected and a trade at the edge of the band halts the
+// book for the cool-off period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    // Fixed for the whole day around the previous close, like the NSE 5/10/20% circuits.
+    pub static_band: Option<Decimal>,
+    // Moves with the last traded price, like the US limit up / limit down bands.
+    pub dynamic_band: Option<Decimal>,
+    // How long the book stays halted after a breach, in milliseconds.
+    pub cool_off: Timestamp,
+}
+
+impl CircuitBreaker {
+    pub fn new(cool_off: Timestamp) -> CircuitBreaker {
+        CircuitBreaker {
+            static_band: None,
+            dynamic_band: None,
+            cool_off,
+        }
+    }
+
+    pub fn with_static_band(mut self, band: Decimal) -> CircuitBreaker {
+        self.static_band = Some(band);
+        self
+    }
+
+    pub fn with_dynamic_band(mut self, band: Decimal) -> CircuitBreaker {
+        self.dynamic_band = Some(band);
+        self
+    }
+
+    // (Lower, Upper) price limit, the tighter of both bands when both apply.
+    // The dynamic band falls back to the previous close until the first trade.
+    pub fn price_band(
+        &self,
+        previous_close: Option<Decimal>,
+        last_trade_price: Option<Decimal>,
+    ) -> Option<(Decimal, Decimal)> {
+        let band_around = |band: Option<Decimal>, reference: Option<Decimal>| {
+            let (band, reference) = (band?, reference?);
+            Some((reference * (dec!(1) - band), reference * (dec!(1) + band)))
+        };
+        let static_band = band_around(self.static_band, previous_close);
+        let dynamic_band = band_around(self.dynamic_band, last_trade_price.or(previous_close));
+        match (static_band, dynamic_band) {
+            (Some((static_lower, static_upper)), Some((dynamic_lower, dynamic_upper))) => Some((
+                static_lower.max(dynamic_lower),
+                static_upper.min(dynamic_upper),
+            )),
+            (band, None) | (None, band) => band,
+        }
+    }
+}
@@ -1,27 +1,42 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use super::orderbook::OrderBook;
+use rust_decimal::Decimal;
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+use super::analytics::Benchmarks;
+use super::candles::{CandleInterval, CandleSeries};
+use super::clock::{Clock, SystemClock, Timestamp};
+use super::error::EngineError;
+use super::instrument::{
+    Instrument, InstrumentId, InstrumentRegistry, InstrumentStatus, TradingRules,
+};
+use super::market_data::Ticker;
+use super::matching::MatchingAlgorithm;
+use super::order::{NewOrderRequest, Order, OrderId, OrderKind, OrderStatus};
+use super::orderbook::{HaltPolicy, OrderBook};
+use super::session::{SessionSchedule, SessionTransition, TradingSession};
+use super::trade::{MatchResult, SubmitResponse, Trade};
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum Market {
     IndianMarket(IndianExchange),
     USMarket(USExchange),
     CryptoMarket(CryptoExchange),
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum IndianExchange {
     NSE,
     BSE,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum USExchange {
     NASDAQ,
     NYSE,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum CryptoExchange {
     WazirX,
     CoinDCX,
@@ -29,7 +44,7 @@ pub enum CryptoExchange {
     Coinbase,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum Sector {
     Technology,
     Finance,
@@ -45,7 +60,7 @@ pub enum Sector {
     Utilities,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct Company {
     name: String,
     symbol: String,
@@ -62,25 +77,375 @@ impl Company {
             market,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn sector(&self) -> &Sector {
+        &self.sector
+    }
+
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
 }
 
 pub struct MatchingEngine {
-    pub orderbooks: HashMap<Company, OrderBook>,
+    // HashMap : [Key : Instrument, Value : Its book]
+    pub orderbooks: HashMap<InstrumentId, OrderBook>,
+    instruments: InstrumentRegistry,
+    // Markets without an entry follow `SessionSchedule::for_market`.
+    session_schedules: HashMap<Market, SessionSchedule>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for MatchingEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MatchingEngine {
     pub fn new() -> MatchingEngine {
+        MatchingEngine::with_clock(Arc::new(SystemClock))
+    }
+
+    // Every book listed by this engine shares its clock, which also drives the trading sessions.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> MatchingEngine {
         MatchingEngine {
             orderbooks: HashMap::new(),
+            instruments: InstrumentRegistry::new(),
+            session_schedules: HashMap::new(),
+            clock,
+        }
+    }
+
+    pub fn set_session_schedule(&mut self, market: Market, schedule: SessionSchedule) {
+        self.session_schedules.insert(market, schedule);
+    }
+
+    pub fn current_session(&self, market: &Market) -> TradingSession {
+        match self.session_schedules.get(market) {
+            Some(schedule) => schedule.session_at(self.clock.now()),
+            None => SessionSchedule::for_market(market).session_at(self.clock.now()),
+        }
+    }
+
+    // Move every book into the session its market is in right now, expire its Good Till Date
+    // orders, resume the books whose circuit breaker cool-off is over and close finished candles.
+    // Returns the transitions of the books which changed.
+    pub fn update_sessions(&mut self) -> Vec<(Company, SessionTransition)> {
+        let mut transitions = Vec::new();
+        let mut ids: Vec<InstrumentId> = self.orderbooks.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            let company = match self.instruments.get(id) {
+                Some(instrument) => instrument.company.clone(),
+                None => continue,
+            };
+            let session = self.current_session(&company.market);
+            let orderbook = match self.orderbooks.get_mut(&id) {
+                Some(orderbook) => orderbook,
+                None => continue,
+            };
+            orderbook.roll_candles();
+            let expired_orders = orderbook.expire_orders();
+            if !expired_orders.is_empty() {
+                let transition = SessionTransition {
+                    expired_orders,
+                    ..SessionTransition::default()
+                };
+                transitions.push((company.clone(), transition));
+            }
+            if let Some(trades) = orderbook.end_cool_off() {
+                let transition = SessionTransition {
+                    trades,
+                    ..SessionTransition::default()
+                };
+                transitions.push((company.clone(), transition));
+            }
+            if orderbook.session() != session {
+                transitions.push((company, orderbook.set_session(session)));
+            }
+            self.sync_status(id);
         }
+        transitions
     }
 
-    pub fn list_new_company(&mut self, company: Company) {
-        let orderbook = OrderBook::new();
-        self.orderbooks.insert(company, orderbook);
+    pub fn instruments(&self) -> &InstrumentRegistry {
+        &self.instruments
+    }
+
+    pub fn instrument(&self, company: &Company) -> Option<&Instrument> {
+        self.instruments.get(self.instruments.id_of(company)?)
+    }
+
+    fn instrument_id(&self, company: &Company) -> Result<InstrumentId, EngineError> {
+        self.instruments
+            .id_of(company)
+            .ok_or(EngineError::UnknownCompany)
+    }
+
+    fn new_orderbook(&self, market: &Market) -> OrderBook {
+        let mut orderbook = OrderBook::with_clock(self.clock.clone());
+        orderbook.set_session(self.current_session(market));
+        orderbook
+    }
+
+    pub fn list_new_company(&mut self, company: Company) -> Result<InstrumentId, EngineError> {
+        self.list_instrument(Instrument::new(company))
+    }
+
+    // List a company whose book allocates fills with something other than price-time priority.
+    pub fn list_new_company_with_algorithm(
+        &mut self,
+        company: Company,
+        matching_algorithm: Box<dyn MatchingAlgorithm>,
+    ) -> Result<InstrumentId, EngineError> {
+        let mut orderbook = self.new_orderbook(&company.market);
+        orderbook.set_matching_algorithm(matching_algorithm);
+        self.list(Instrument::new(company), orderbook)
+    }
+
+    pub fn list_instrument(&mut self, instrument: Instrument) -> Result<InstrumentId, EngineError> {
+        let orderbook = self.new_orderbook(&instrument.company.market);
+        self.list(instrument, orderbook)
+    }
+
+    // Listing a company twice would silently throw its book away.
+    fn list(
+        &mut self,
+        mut instrument: Instrument,
+        mut orderbook: OrderBook,
+    ) -> Result<InstrumentId, EngineError> {
+        if instrument.listing_date == 0 {
+            instrument.listing_date = self.clock.now();
+        }
+        orderbook.set_tick_table(instrument.trading_rules.tick_table.clone());
+        let id = self.instruments.register(instrument)?;
+        self.orderbooks.insert(id, orderbook);
+        Ok(id)
+    }
+
+    // Replace the tick, lot and order size limits of a listed company.
+    pub fn set_trading_rules(
+        &mut self,
+        company: &Company,
+        trading_rules: TradingRules,
+    ) -> Result<(), EngineError> {
+        let id = self.instrument_id(company)?;
+        self.listed_orderbook(id)?
+            .set_tick_table(trading_rules.tick_table.clone());
+        if let Some(instrument) = self.instruments.get_mut(id) {
+            instrument.trading_rules = trading_rules;
+        }
+        Ok(())
+    }
+
+    pub fn trading_rules(&self, company: &Company) -> Option<&TradingRules> {
+        Some(&self.instrument(company)?.trading_rules)
+    }
+
+    // Check an order against the trading rules of the company and hand it to its book.
+    // Orders rejected by the book itself, e.g. outside trading hours, are returned as errors too.
+    pub fn place_order(
+        &mut self,
+        company: &Company,
+        order: Order,
+    ) -> Result<MatchResult, EngineError> {
+        let id = self.instrument_id(company)?;
+        self.place(id, order)
+    }
+
+    fn place(&mut self, id: InstrumentId, mut order: Order) -> Result<MatchResult, EngineError> {
+        self.listed_trading_rules(id)?.validate(&order)?;
+        let orderbook = self.listed_orderbook(id)?;
+        let result = match order.kind {
+            OrderKind::Limit => orderbook.match_limit_order(&mut order),
+            OrderKind::Market | OrderKind::MarketToLimit => {
+                orderbook.match_market_order(&mut order)
+            }
+            OrderKind::Stop { .. } | OrderKind::StopLimit { .. } => orderbook.add_stop_order(order),
+        };
+        self.sync_status(id);
+        Self::accepted(result)
+    }
+
+    // Orders rejected by the book are returned as errors, the same way as rejections by the
+    // engine, so that callers only need to check for `Err`.
+    fn accepted(result: MatchResult) -> Result<MatchResult, EngineError> {
+        match result.reject_reason {
+            Some(reason) if result.order.status == OrderStatus::Rejected => Err(reason.into()),
+            _ => Ok(result),
+        }
+    }
+
+    // Route an order to the book of its symbol, on the requested exchange if there is one.
+    pub fn submit(
+        &mut self,
+        symbol: &str,
+        request: NewOrderRequest,
+    ) -> Result<SubmitResponse, EngineError> {
+        let id = self.instruments.find(symbol, request.market.as_ref())?;
+        let result = self.place(id, request.order)?;
+        Ok(SubmitResponse::new(symbol.to_string(), result))
+    }
+
+    pub fn get_orderbook(&mut self, id: InstrumentId) -> Option<&mut OrderBook> {
+        self.orderbooks.get_mut(&id)
     }
 
     pub fn get_company_orderbook(&mut self, company: &Company) -> Option<&mut OrderBook> {
-        self.orderbooks.get_mut(company)
+        let id = self.instruments.id_of(company)?;
+        self.orderbooks.get_mut(&id)
+    }
+
+    pub fn ticker(&self, company: &Company) -> Option<Ticker> {
+        let id = self.instruments.id_of(company)?;
+        Some(self.orderbooks.get(&id)?.ticker())
+    }
+
+    pub fn candles(&self, company: &Company, interval: CandleInterval) -> Option<&CandleSeries> {
+        let id = self.instruments.id_of(company)?;
+        self.orderbooks.get(&id)?.candles().series(interval)
+    }
+
+    pub fn session_benchmarks(&self, company: &Company) -> Option<Benchmarks> {
+        let id = self.instruments.id_of(company)?;
+        Some(self.orderbooks.get(&id)?.session_benchmarks())
+    }
+
+    pub fn rolling_benchmarks(&self, company: &Company, window: Timestamp) -> Option<Benchmarks> {
+        let id = self.instruments.id_of(company)?;
+        Some(self.orderbooks.get(&id)?.rolling_benchmarks(window))
+    }
+
+    // Tickers of every listed company, in listing order.
+    pub fn tickers(&self) -> Vec<(Company, Ticker)> {
+        self.instruments
+            .listed()
+            .filter_map(|instrument| {
+                let orderbook = self.orderbooks.get(&instrument.id)?;
+                Some((instrument.company.clone(), orderbook.ticker()))
+            })
+            .collect()
+    }
+
+    fn listed_orderbook(&mut self, id: InstrumentId) -> Result<&mut OrderBook, EngineError> {
+        self.orderbooks
+            .get_mut(&id)
+            .ok_or(EngineError::UnknownCompany)
+    }
+
+    fn listed_trading_rules(&self, id: InstrumentId) -> Result<&TradingRules, EngineError> {
+        self.instruments
+            .get(id)
+            .map(|instrument| &instrument.trading_rules)
+            .ok_or(EngineError::UnknownCompany)
+    }
+
+    fn company_orderbook(&mut self, company: &Company) -> Result<&mut OrderBook, EngineError> {
+        let id = self.instrument_id(company)?;
+        self.listed_orderbook(id)
+    }
+
+    pub fn cancel_order(&mut self, company: &Company, id: OrderId) -> Result<Order, EngineError> {
+        self.company_orderbook(company)?.cancel_order(id)
+    }
+
+    // Amendments are checked against the trading rules of the company like new orders.
+    pub fn modify_quantity(
+        &mut self,
+        company: &Company,
+        id: OrderId,
+        new_quantity: Decimal,
+    ) -> Result<Order, EngineError> {
+        let instrument_id = self.instrument_id(company)?;
+        self.listed_trading_rules(instrument_id)?
+            .validate_quantity(new_quantity)?;
+        self.listed_orderbook(instrument_id)?
+            .modify_quantity(id, new_quantity)
+    }
+
+    pub fn replace_order(
+        &mut self,
+        company: &Company,
+        id: OrderId,
+        new_price: Decimal,
+        new_quantity: Decimal,
+    ) -> Result<MatchResult, EngineError> {
+        let instrument_id = self.instrument_id(company)?;
+        let order = self
+            .listed_orderbook(instrument_id)?
+            .get_order(id)
+            .ok_or(EngineError::UnknownOrder(id))?;
+        let replaced_order = Order {
+            price: new_price,
+            quantity: new_quantity,
+            ..order.clone()
+        };
+        self.listed_trading_rules(instrument_id)?
+            .validate(&replaced_order)?;
+        let result =
+            self.listed_orderbook(instrument_id)?
+                .replace_order(id, new_price, new_quantity);
+        self.sync_status(instrument_id);
+        Self::accepted(result?)
+    }
+
+    // Every halt and resume, by the operator or the circuit breaker of the book, ends up in
+    // the status of the instrument.
+    fn sync_status(&mut self, id: InstrumentId) {
+        let halted = match self.orderbooks.get(&id) {
+            Some(orderbook) => orderbook.is_halted(),
+            None => return,
+        };
+        if let Some(instrument) = self.instruments.get_mut(id) {
+            instrument.status = match (instrument.status, halted) {
+                (InstrumentStatus::Active, true) => InstrumentStatus::Halted,
+                (InstrumentStatus::Halted, false) => InstrumentStatus::Active,
+                (status, _) => status,
+            };
+        }
+    }
+
+    pub fn halt_company(
+        &mut self,
+        company: &Company,
+        policy: HaltPolicy,
+    ) -> Result<(), EngineError> {
+        let id = self.instrument_id(company)?;
+        self.listed_orderbook(id)?.halt(policy);
+        self.sync_status(id);
+        Ok(())
+    }
+
+    // Resume a halted company through a re-opening auction and return its trades.
+    pub fn resume_company(&mut self, company: &Company) -> Result<Vec<Trade>, EngineError> {
+        let id = self.instrument_id(company)?;
+        let orderbook = self.listed_orderbook(id)?;
+        if !orderbook.is_halted() {
+            return Err(EngineError::NotHalted);
+        }
+        let trades = orderbook.resume();
+        self.sync_status(id);
+        Ok(trades)
+    }
+
+    // Cancel every order of the company and remove its book. Returns the cancelled orders.
+    // The instrument stays in the registry as delisted.
+    pub fn delist_company(&mut self, company: &Company) -> Result<Vec<Order>, EngineError> {
+        let id = self.instrument_id(company)?;
+        let mut orderbook = self
+            .orderbooks
+            .remove(&id)
+            .ok_or(EngineError::UnknownCompany)?;
+        self.instruments.delist(id);
+        Ok(orderbook.cancel_all_orders())
     }
 }
@@ -0,0 +1,92 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+
+use super::order::OrderId;
+use super::session::TradingSession;
+
+// Why an order was turned away, either before it reached the book or by the book itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    NonPositiveQuantity,
+    NonPositivePrice,
+    // An iceberg order has to show some of its quantity.
+    NonPositiveDisplayQuantity,
+    PriceNotOnTick { tick_size: Decimal },
+    QuantityNotInLots { lot_size: Decimal },
+    BelowMinimumQuantity { minimum_quantity: Decimal },
+    AboveMaximumQuantity { maximum_quantity: Decimal },
+    // The current trading session does not take this kind of order.
+    NotAcceptedInSession(TradingSession),
+    InstrumentHalted,
+    OutsidePriceBand { lower: Decimal, upper: Decimal },
+    // A post-only order which would have taken liquidity.
+    WouldTakeLiquidity,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::NonPositiveQuantity => write!(f, "quantity must be positive"),
+            RejectReason::NonPositivePrice => write!(f, "price must be positive"),
+            RejectReason::NonPositiveDisplayQuantity => {
+                write!(f, "display quantity must be positive")
+            }
+            RejectReason::PriceNotOnTick { tick_size } => {
+                write!(f, "price is not a multiple of the tick size {}", tick_size)
+            }
+            RejectReason::QuantityNotInLots { lot_size } => {
+                write!(f, "quantity is not a multiple of the lot size {}", lot_size)
+            }
+            RejectReason::BelowMinimumQuantity { minimum_quantity } => {
+                write!(f, "quantity is below the minimum of {}", minimum_quantity)
+            }
+            RejectReason::AboveMaximumQuantity { maximum_quantity } => {
+                write!(f, "quantity is above the maximum of {}", maximum_quantity)
+            }
+            RejectReason::NotAcceptedInSession(session) => {
+                write!(f, "order type not accepted during {:?}", session)
+            }
+            RejectReason::InstrumentHalted => write!(f, "instrument is halted"),
+            RejectReason::OutsidePriceBand { lower, upper } => {
+                write!(f, "price is outside the band {} - {}", lower, upper)
+            }
+            RejectReason::WouldTakeLiquidity => write!(f, "post-only order would take liquidity"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    UnknownCompany,
+    // The symbol is listed on several exchanges and the request did not name one.
+    AmbiguousSymbol,
+    AlreadyListed,
+    // No open order with this id, it may have traded or been cancelled already.
+    UnknownOrder(OrderId),
+    NotHalted,
+    Rejected(RejectReason),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::UnknownCompany => write!(f, "company is not listed"),
+            EngineError::AmbiguousSymbol => {
+                write!(f, "symbol is listed on several exchanges")
+            }
+            EngineError::AlreadyListed => write!(f, "company is already listed"),
+            EngineError::UnknownOrder(id) => write!(f, "no open order with id {}", id.0),
+            EngineError::NotHalted => write!(f, "company is not halted"),
+            EngineError::Rejected(reason) => write!(f, "order rejected : {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<RejectReason> for EngineError {
+    fn from(reason: RejectReason) -> Self {
+        EngineError::Rejected(reason)
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::clock::Timestamp;
+use super::engine::{Company, Market, Sector};
+use super::error::{EngineError, RejectReason};
+use super::order::{Order, OrderKind};
+
+// Price dependent tick sizes like the NSE and SEBI tick tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickTable {
+    // (Lowest price the tick size applies from, Tick size) sorted by price.
+    ticks: Vec<(Decimal, Decimal)>,
+}
+
+impl TickTable {
+    pub fn new(mut ticks: Vec<(Decimal, Decimal)>) -> TickTable {
+        ticks.sort_by_key(|(from_price, _)| *from_price);
+        TickTable { ticks }
+    }
+
+    pub fn fixed(tick_size: Decimal) -> TickTable {
+        TickTable::new(vec![(dec!(0), tick_size)])
+    }
+
+    pub fn tick_size(&self, price: Decimal) -> Decimal {
+        self.ticks
+            .iter()
+            .take_while(|(from_price, _)| *from_price <= price)
+            .last()
+            .or_else(|| self.ticks.first())
+            .map_or(dec!(0), |(_, tick_size)| *tick_size)
+    }
+
+    pub fn is_on_tick(&self, price: Decimal) -> bool {
+        let tick_size = self.tick_size(price);
+        tick_size <= dec!(0) || (price % tick_size).is_zero()
+    }
+}
+
+impl Default for TickTable {
+    fn default() -> Self {
+        TickTable::fixed(dec!(0.01))
+    }
+}
+
+// Static order entry limits of an instrument.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TradingRules {
+    pub tick_table: TickTable,
+    pub lot_size: Option<Decimal>,
+    pub minimum_quantity: Option<Decimal>,
+    pub maximum_quantity: Option<Decimal>,
+}
+
+impl TradingRules {
+    pub fn new(tick_table: TickTable) -> TradingRules {
+        TradingRules {
+            tick_table,
+            ..TradingRules::default()
+        }
+    }
+
+    pub fn with_lot_size(mut self, lot_size: Decimal) -> TradingRules {
+        self.lot_size = Some(lot_size);
+        self
+    }
+
+    pub fn with_minimum_quantity(mut self, minimum_quantity: Decimal) -> TradingRules {
+        self.minimum_quantity = Some(minimum_quantity);
+        self
+    }
+
+    pub fn with_maximum_quantity(mut self, maximum_quantity: Decimal) -> TradingRules {
+        self.maximum_quantity = Some(maximum_quantity);
+        self
+    }
+
+    pub fn validate(&self, order: &Order) -> Result<(), RejectReason> {
+        self.validate_quantity(order.quantity)?;
+        if order
+            .display_quantity
+            .is_some_and(|display_quantity| display_quantity <= dec!(0))
+        {
+            return Err(RejectReason::NonPositiveDisplayQuantity);
+        }
+        // Market orders carry no limit price, stop orders are checked on their stop price too.
+        let prices = match order.kind {
+            OrderKind::Limit => vec![order.price],
+            OrderKind::Market | OrderKind::MarketToLimit => vec![],
+            OrderKind::Stop { stop_price } => vec![stop_price],
+            OrderKind::StopLimit { stop_price } => vec![stop_price, order.price],
+        };
+        for price in prices {
+            if price <= dec!(0) {
+                return Err(RejectReason::NonPositivePrice);
+            }
+            if !self.tick_table.is_on_tick(price) {
+                return Err(RejectReason::PriceNotOnTick {
+                    tick_size: self.tick_table.tick_size(price),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate_quantity(&self, quantity: Decimal) -> Result<(), RejectReason> {
+        if quantity <= dec!(0) {
+            return Err(RejectReason::NonPositiveQuantity);
+        }
+        // Like tick sizes, lot sizes which are not positive do not restrict anything.
+        if let Some(lot_size) = self.lot_size.filter(|lot_size| *lot_size > dec!(0)) {
+            if !(quantity % lot_size).is_zero() {
+                return Err(RejectReason::QuantityNotInLots { lot_size });
+            }
+        }
+        if let Some(minimum_quantity) = self.minimum_quantity {
+            if quantity < minimum_quantity {
+                return Err(RejectReason::BelowMinimumQuantity { minimum_quantity });
+            }
+        }
+        if let Some(maximum_quantity) = self.maximum_quantity {
+            if quantity > maximum_quantity {
+                return Err(RejectReason::AboveMaximumQuantity { maximum_quantity });
+            }
+        }
+        Ok(())
+    }
+}
+
+// Engine assigned identifier of a listing. Ids are never reused, not even after a delisting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct InstrumentId(pub u64);
+
+// Administrative status of a listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstrumentStatus {
+    Active,
+    Halted,
+    Delisted,
+}
+
+// Reference data of a listed instrument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrument {
+    // `InstrumentId(0)` until the instrument is listed.
+    pub id: InstrumentId,
+    pub isin: Option<String>,
+    pub company: Company,
+    pub currency: String,
+    pub trading_rules: TradingRules,
+    pub status: InstrumentStatus,
+    // Stamped with the listing time unless given.
+    pub listing_date: Timestamp,
+}
+
+impl Instrument {
+    pub fn new(company: Company) -> Instrument {
+        let currency = match company.market() {
+            Market::IndianMarket(_) => "INR",
+            Market::USMarket(_) => "USD",
+            Market::CryptoMarket(_) => "USDT",
+        };
+        Instrument {
+            id: InstrumentId::default(),
+            isin: None,
+            currency: currency.to_string(),
+            company,
+            trading_rules: TradingRules::default(),
+            status: InstrumentStatus::Active,
+            listing_date: 0,
+        }
+    }
+
+    pub fn with_isin(mut self, isin: String) -> Instrument {
+        self.isin = Some(isin);
+        self
+    }
+
+    pub fn with_currency(mut self, currency: String) -> Instrument {
+        self.currency = currency;
+        self
+    }
+
+    pub fn with_trading_rules(mut self, trading_rules: TradingRules) -> Instrument {
+        self.trading_rules = trading_rules;
+        self
+    }
+
+    pub fn with_listing_date(mut self, listing_date: Timestamp) -> Instrument {
+        self.listing_date = listing_date;
+        self
+    }
+
+    pub fn symbol(&self) -> &str {
+        self.company.symbol()
+    }
+
+    pub fn is_listed(&self) -> bool {
+        self.status != InstrumentStatus::Delisted
+    }
+}
+
+// Every instrument the engine has ever listed. Queries only return listed instruments.
+pub struct InstrumentRegistry {
+    instruments: BTreeMap<InstrumentId, Instrument>,
+    // Listed companies : [Key : Company, Value : Its current listing]
+    listed: HashMap<Company, InstrumentId>,
+    next_instrument_id: u64,
+}
+
+impl Default for InstrumentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstrumentRegistry {
+    pub fn new() -> InstrumentRegistry {
+        InstrumentRegistry {
+            instruments: BTreeMap::new(),
+            listed: HashMap::new(),
+            next_instrument_id: 1,
+        }
+    }
+
+    pub fn register(&mut self, mut instrument: Instrument) -> Result<InstrumentId, EngineError> {
+        if self.listed.contains_key(&instrument.company) {
+            return Err(EngineError::AlreadyListed);
+        }
+        let id = InstrumentId(self.next_instrument_id);
+        self.next_instrument_id += 1;
+        instrument.id = id;
+        instrument.status = InstrumentStatus::Active;
+        self.listed.insert(instrument.company.clone(), id);
+        self.instruments.insert(id, instrument);
+        Ok(id)
+    }
+
+    // The instrument keeps its reference data, but its company can be listed again.
+    pub fn delist(&mut self, id: InstrumentId) -> Option<&Instrument> {
+        let instrument = self.instruments.get_mut(&id)?;
+        instrument.status = InstrumentStatus::Delisted;
+        self.listed.remove(&instrument.company);
+        Some(instrument)
+    }
+
+    pub fn get(&self, id: InstrumentId) -> Option<&Instrument> {
+        self.instruments.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: InstrumentId) -> Option<&mut Instrument> {
+        self.instruments.get_mut(&id)
+    }
+
+    pub fn id_of(&self, company: &Company) -> Option<InstrumentId> {
+        self.listed.get(company).copied()
+    }
+
+    // Listed instrument of a symbol, on `market` when the symbol trades on several exchanges.
+    pub fn find(&self, symbol: &str, market: Option<&Market>) -> Result<InstrumentId, EngineError> {
+        let mut instruments = self
+            .by_symbol(symbol)
+            .into_iter()
+            .filter(|instrument| market.is_none_or(|market| instrument.company.market() == market));
+        let instrument = instruments.next().ok_or(EngineError::UnknownCompany)?;
+        if instruments.next().is_some() {
+            return Err(EngineError::AmbiguousSymbol);
+        }
+        Ok(instrument.id)
+    }
+
+    // Delisted instruments included, ordered by id.
+    pub fn instruments(&self) -> impl Iterator<Item = &Instrument> {
+        self.instruments.values()
+    }
+
+    pub fn listed(&self) -> impl Iterator<Item = &Instrument> {
+        self.instruments
+            .values()
+            .filter(|instrument| instrument.is_listed())
+    }
+
+    pub fn by_symbol(&self, symbol: &str) -> Vec<&Instrument> {
+        self.listed()
+            .filter(|instrument| instrument.symbol() == symbol)
+            .collect()
+    }
+
+    pub fn by_sector(&self, sector: &Sector) -> Vec<&Instrument> {
+        self.listed()
+            .filter(|instrument| instrument.company.sector() == sector)
+            .collect()
+    }
+
+    pub fn by_market(&self, market: &Market) -> Vec<&Instrument> {
+        self.listed()
+            .filter(|instrument| instrument.company.market() == market)
+            .collect()
+    }
+
+    // e.g. all Banking stocks on NSE.
+    pub fn by_sector_and_market(&self, sector: &Sector, market: &Market) -> Vec<&Instrument> {
+        self.listed()
+            .filter(|instrument| {
+                instrument.company.sector() == sector && instrument.company.market() == market
+            })
+            .collect()
+    }
+}
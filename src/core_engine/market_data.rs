@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::clock::Timestamp;
+use super::order::{BuyOrSell, OrderId};
+
+// Aggregated visible interest at one price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthLevel {
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub order_count: usize,
+}
+
+// Market by price view of a book, best levels first on both sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthSnapshot {
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+    pub timestamp: Timestamp,
+}
+
+impl DepthSnapshot {
+    pub fn best_bid(&self) -> Option<&DepthLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&DepthLevel> {
+        self.asks.first()
+    }
+
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    pub fn mid_price(&self) -> Option<Decimal> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / dec!(2))
+    }
+}
+
+// Trading activity of the current trading day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SessionStatistics {
+    pub open: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    // Price of the latest trade of the day.
+    pub close: Option<Decimal>,
+    pub volume: Decimal,
+    // Traded value, the sum of price times quantity.
+    pub turnover: Decimal,
+    pub trade_count: u64,
+}
+
+impl SessionStatistics {
+    pub fn record_trade(&mut self, price: Decimal, quantity: Decimal) {
+        self.open = self.open.or(Some(price));
+        self.high = Some(self.high.map_or(price, |high| high.max(price)));
+        self.low = Some(self.low.map_or(price, |low| low.min(price)));
+        self.close = Some(price);
+        self.volume += quantity;
+        self.turnover += price * quantity;
+        self.trade_count += 1;
+    }
+
+    pub fn vwap(&self) -> Option<Decimal> {
+        if self.volume.is_zero() {
+            return None;
+        }
+        Some(self.turnover / self.volume)
+    }
+}
+
+// Level 1 view of a book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticker {
+    pub best_bid: Option<DepthLevel>,
+    pub best_ask: Option<DepthLevel>,
+    pub last_trade_price: Option<Decimal>,
+    pub last_trade_quantity: Option<Decimal>,
+    pub statistics: SessionStatistics,
+    pub previous_close: Option<Decimal>,
+    pub timestamp: Timestamp,
+}
+
+impl Ticker {
+    // Last traded price against the previous close.
+    pub fn change(&self) -> Option<Decimal> {
+        Some(self.last_trade_price? - self.previous_close?)
+    }
+
+    pub fn change_percentage(&self) -> Option<Decimal> {
+        let previous_close = self.previous_close.filter(|price| !price.is_zero())?;
+        Some(self.change()? / previous_close * dec!(100))
+    }
+}
+
+// Incremental change to the displayed orders of a book. Hidden orders are never published
+// and iceberg orders only with their displayed peak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookEvent {
+    // Joins the back of its price level.
+    OrderAdded {
+        id: OrderId,
+        side: BuyOrSell,
+        price: Decimal,
+        quantity: Decimal,
+    },
+    // Open quantity reduced in place without losing time priority, `quantity` is what is left.
+    OrderReduced {
+        id: OrderId,
+        side: BuyOrSell,
+        price: Decimal,
+        quantity: Decimal,
+    },
+    OrderCancelled {
+        id: OrderId,
+        side: BuyOrSell,
+        price: Decimal,
+    },
+    // A resting order traded `quantity`, it leaves the book once nothing is displayed anymore.
+    OrderExecuted {
+        id: OrderId,
+        side: BuyOrSell,
+        price: Decimal,
+        quantity: Decimal,
+    },
+    // The last displayed order at this price has left the book.
+    LevelCleared {
+        side: BuyOrSell,
+        price: Decimal,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookUpdate {
+    // Gapless, starting at 1 for every book.
+    pub sequence: u64,
+    pub timestamp: Timestamp,
+    pub event: BookEvent,
+}
+
+// Market by order view of a book : a snapshot taken from the book, or a copy kept up to date
+// by a remote consumer applying the updates which follow the snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MarketByOrder {
+    // Sequence of the last update included.
+    pub sequence: u64,
+    // BTreeMap : [Key : Price, Value : (OrderId, Displayed quantity) in time priority]
+    pub bids: BTreeMap<Decimal, Vec<(OrderId, Decimal)>>,
+    pub asks: BTreeMap<Decimal, Vec<(OrderId, Decimal)>>,
+}
+
+impl MarketByOrder {
+    // Updates already included in the view are skipped.
+    pub fn apply(&mut self, update: &BookUpdate) {
+        if update.sequence <= self.sequence {
+            return;
+        }
+        self.sequence = update.sequence;
+        match update.event {
+            BookEvent::OrderAdded {
+                id,
+                side,
+                price,
+                quantity,
+            } => self
+                .levels_mut(side)
+                .entry(price)
+                .or_default()
+                .push((id, quantity)),
+            BookEvent::OrderReduced {
+                id,
+                side,
+                price,
+                quantity,
+            } => self.update_order(side, price, id, |_| quantity),
+            BookEvent::OrderExecuted {
+                id,
+                side,
+                price,
+                quantity,
+            } => self.update_order(side, price, id, |open_quantity| open_quantity - quantity),
+            BookEvent::OrderCancelled { id, side, price } => {
+                self.update_order(side, price, id, |_| dec!(0))
+            }
+            BookEvent::LevelCleared { side, price } => {
+                self.levels_mut(side).remove(&price);
+            }
+        }
+    }
+
+    fn levels_mut(&mut self, side: BuyOrSell) -> &mut BTreeMap<Decimal, Vec<(OrderId, Decimal)>> {
+        match side {
+            BuyOrSell::Buy => &mut self.bids,
+            BuyOrSell::Sell => &mut self.asks,
+        }
+    }
+
+    // Orders with nothing left are removed, and so are empty levels.
+    fn update_order(
+        &mut self,
+        side: BuyOrSell,
+        price: Decimal,
+        id: OrderId,
+        new_quantity: impl Fn(Decimal) -> Decimal,
+    ) {
+        let levels = self.levels_mut(side);
+        if let Some(orders) = levels.get_mut(&price) {
+            if let Some(position) = orders.iter().position(|(order_id, _)| *order_id == id) {
+                let quantity = new_quantity(orders[position].1);
+                if quantity > dec!(0) {
+                    orders[position].1 = quantity;
+                } else {
+                    orders.remove(position);
+                }
+            }
+            if orders.is_empty() {
+                levels.remove(&price);
+            }
+        }
+    }
+}
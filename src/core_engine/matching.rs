@@ -0,0 +1,121 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::order::Order;
+
+// Decides how an incoming quantity is shared between the resting orders of one price level.
+pub trait MatchingAlgorithm: Send + Sync {
+    // `resting_orders` are given in time priority. Returns the quantity allocated to each of
+    // them, in the same order, never more than an order's displayed quantity and never more
+    // than `quantity` in total.
+    fn allocate(&self, resting_orders: &[&Order], quantity: Decimal) -> Vec<Decimal>;
+}
+
+// Price-time priority : the oldest order is filled completely before the next one trades.
+pub struct Fifo;
+
+impl MatchingAlgorithm for Fifo {
+    fn allocate(&self, resting_orders: &[&Order], quantity: Decimal) -> Vec<Decimal> {
+        let mut allocations = vec![dec!(0); resting_orders.len()];
+        fill_in_time_priority(resting_orders, &mut allocations, quantity);
+        allocations
+    }
+}
+
+// Every order gets a share proportional to its size, rounded down to `lot_size`.
+// Shares below `minimum_allocation` are dropped and whatever is left after rounding
+// is handed out in time priority.
+pub struct ProRata {
+    pub lot_size: Decimal,
+    pub minimum_allocation: Decimal,
+}
+
+impl ProRata {
+    pub fn new(lot_size: Decimal, minimum_allocation: Decimal) -> ProRata {
+        ProRata {
+            lot_size,
+            minimum_allocation,
+        }
+    }
+}
+
+impl MatchingAlgorithm for ProRata {
+    fn allocate(&self, resting_orders: &[&Order], quantity: Decimal) -> Vec<Decimal> {
+        let mut allocations = vec![dec!(0); resting_orders.len()];
+        let total: Decimal = resting_orders.iter().map(|order| order.quantity).sum();
+        if total <= quantity {
+            // Enough to fill everybody.
+            return resting_orders.iter().map(|order| order.quantity).collect();
+        }
+        for (allocation, order) in allocations.iter_mut().zip(resting_orders) {
+            let share = quantity * order.quantity / total;
+            let share = if self.lot_size > dec!(0) {
+                (share / self.lot_size).floor() * self.lot_size
+            } else {
+                share
+            };
+            if share >= self.minimum_allocation {
+                *allocation = share.min(order.quantity);
+            }
+        }
+        let allocated: Decimal = allocations.iter().sum();
+        fill_in_time_priority(resting_orders, &mut allocations, quantity - allocated);
+        allocations
+    }
+}
+
+// The first order in the queue gets `top_order_percentage` of the incoming quantity before
+// the remainder is shared pro-rata, as used by several futures exchanges.
+pub struct TopOrderProRata {
+    pub top_order_percentage: Decimal,
+    pub pro_rata: ProRata,
+}
+
+impl TopOrderProRata {
+    pub fn new(top_order_percentage: Decimal, pro_rata: ProRata) -> TopOrderProRata {
+        TopOrderProRata {
+            top_order_percentage,
+            pro_rata,
+        }
+    }
+}
+
+impl MatchingAlgorithm for TopOrderProRata {
+    fn allocate(&self, resting_orders: &[&Order], quantity: Decimal) -> Vec<Decimal> {
+        let top_order = match resting_orders.first() {
+            Some(top_order) => top_order,
+            None => return Vec::new(),
+        };
+        let mut top_allocation = (quantity * self.top_order_percentage).min(top_order.quantity);
+        if self.pro_rata.lot_size > dec!(0) {
+            top_allocation =
+                (top_allocation / self.pro_rata.lot_size).floor() * self.pro_rata.lot_size;
+        }
+        // Share the rest as if the top order had already been reduced.
+        let mut reduced_top_order = (*top_order).clone();
+        reduced_top_order.quantity -= top_allocation;
+        let mut remaining_orders: Vec<&Order> = resting_orders.to_vec();
+        remaining_orders[0] = &reduced_top_order;
+        let mut allocations = self
+            .pro_rata
+            .allocate(&remaining_orders, quantity - top_allocation);
+        allocations[0] += top_allocation;
+        allocations
+    }
+}
+
+// Top up allocations in time priority until `quantity` is used up or every order is full.
+fn fill_in_time_priority(
+    resting_orders: &[&Order],
+    allocations: &mut [Decimal],
+    mut quantity: Decimal,
+) {
+    for (allocation, order) in allocations.iter_mut().zip(resting_orders) {
+        if quantity <= dec!(0) {
+            break;
+        }
+        let extra = (order.quantity - *allocation).min(quantity);
+        *allocation += extra;
+        quantity -= extra;
+    }
+}
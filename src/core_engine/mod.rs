@@ -1,3 +1,14 @@
+pub mod analytics;
+pub mod auction;
+pub mod candles;
+pub mod circuit_breaker;
+pub mod clock;
 pub mod engine;
+pub mod error;
+pub mod instrument;
+pub mod market_data;
+pub mod matching;
 pub mod order;
 pub mod orderbook;
+pub mod session;
+pub mod trade;
@@ -1,24 +1,248 @@
 use rust_decimal::Decimal;
 
-#[derive(Clone)]
+use super::clock::Timestamp;
+use super::engine::Market;
+use rust_decimal_macros::dec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BuyOrSell {
     Buy,
     Sell,
 }
 
-#[derive(Clone)]
+// Engine assigned identifier. `OrderId(0)` means the order has not been accepted by a book yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct OrderId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+    Expired,
+}
+
+impl OrderStatus {
+    // Terminal orders can never trade again.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled
+                | OrderStatus::Cancelled
+                | OrderStatus::Rejected
+                | OrderStatus::Expired
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderKind {
+    Limit,
+    Market,
+    // Market order whose unfilled remainder rests as a limit order at the last traded price.
+    MarketToLimit,
+    // Becomes a market order once the last traded price reaches the stop price.
+    Stop { stop_price: Decimal },
+    // Becomes a limit order at `price` once the last traded price reaches the stop price.
+    StopLimit { stop_price: Decimal },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    GoodTillCancelled,
+    // Trade whatever is possible straight away and cancel the rest.
+    ImmediateOrCancel,
+    // Trade the whole quantity straight away or nothing at all.
+    FillOrKill,
+    // Rest until the end of the trading session.
+    Day,
+    GoodTillDate(Timestamp),
+}
+
+impl TimeInForce {
+    // Whether an unfilled remainder may rest in the book.
+    pub fn can_rest(&self) -> bool {
+        !matches!(
+            self,
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill
+        )
+    }
+}
+
+// What a post-only order does when it would take liquidity on arrival.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostOnly {
+    Reject,
+    // Reprice one tick away from the opposite best price.
+    Slide,
+}
+
+#[derive(Debug, Clone)]
 pub struct Order {
+    pub id: OrderId,
+    pub client_order_id: Option<String>,
+    // Trading account, used for self trade prevention.
+    pub account_id: Option<String>,
+    // Open quantity, for a resting iceberg order only its displayed peak.
     pub quantity: Decimal,
+    // Iceberg reserve which is not displayed in the book.
+    pub hidden_quantity: Decimal,
+    // Peak size of an iceberg order.
+    pub display_quantity: Option<Decimal>,
+    pub filled_quantity: Decimal,
     pub price: Decimal,
     pub order_type: BuyOrSell,
+    pub kind: OrderKind,
+    pub time_in_force: TimeInForce,
+    pub post_only: Option<PostOnly>,
+    // Hidden orders rest in the book without being displayed anywhere.
+    pub hidden: bool,
+    pub status: OrderStatus,
+    // Time priority within a price level, lower sequence numbers trade first.
+    pub sequence: u64,
+    // Arrival time at the book.
+    pub timestamp: Timestamp,
 }
 
 impl Order {
     pub fn new(quantity: Decimal, price: Decimal, order_type: BuyOrSell) -> Order {
         Order {
+            id: OrderId::default(),
+            client_order_id: None,
+            account_id: None,
             quantity,
+            hidden_quantity: dec!(0),
+            display_quantity: None,
+            filled_quantity: dec!(0),
             price,
             order_type,
+            kind: OrderKind::Limit,
+            time_in_force: TimeInForce::GoodTillCancelled,
+            post_only: None,
+            hidden: false,
+            status: OrderStatus::New,
+            sequence: 0,
+            timestamp: 0,
         }
     }
+
+    // Market orders carry no limit price.
+    pub fn market(quantity: Decimal, order_type: BuyOrSell) -> Order {
+        Order {
+            kind: OrderKind::Market,
+            ..Order::new(quantity, dec!(0), order_type)
+        }
+    }
+
+    pub fn market_to_limit(quantity: Decimal, order_type: BuyOrSell) -> Order {
+        Order {
+            kind: OrderKind::MarketToLimit,
+            ..Order::new(quantity, dec!(0), order_type)
+        }
+    }
+
+    pub fn stop(quantity: Decimal, stop_price: Decimal, order_type: BuyOrSell) -> Order {
+        Order {
+            kind: OrderKind::Stop { stop_price },
+            ..Order::new(quantity, dec!(0), order_type)
+        }
+    }
+
+    pub fn stop_limit(
+        quantity: Decimal,
+        stop_price: Decimal,
+        price: Decimal,
+        order_type: BuyOrSell,
+    ) -> Order {
+        Order {
+            kind: OrderKind::StopLimit { stop_price },
+            ..Order::new(quantity, price, order_type)
+        }
+    }
+
+    pub fn with_client_order_id(mut self, client_order_id: String) -> Order {
+        self.client_order_id = Some(client_order_id);
+        self
+    }
+
+    pub fn with_account_id(mut self, account_id: String) -> Order {
+        self.account_id = Some(account_id);
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Order {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_post_only(mut self, post_only: PostOnly) -> Order {
+        self.post_only = Some(post_only);
+        self
+    }
+
+    pub fn with_hidden(mut self, hidden: bool) -> Order {
+        self.hidden = hidden;
+        self
+    }
+
+    pub fn with_display_quantity(mut self, display_quantity: Decimal) -> Order {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    // Displayed plus reserve quantity still to be filled.
+    pub fn leaves_quantity(&self) -> Decimal {
+        self.quantity + self.hidden_quantity
+    }
+
+    // Bring the next tranche of an iceberg reserve into display.
+    pub fn replenish(&mut self) {
+        let tranche = match self.display_quantity {
+            Some(display_quantity) => display_quantity.min(self.hidden_quantity),
+            None => self.hidden_quantity,
+        };
+        self.quantity += tranche;
+        self.hidden_quantity -= tranche;
+    }
+
+    // Consume `quantity` from the open quantity and move the order along its lifecycle.
+    pub fn fill(&mut self, quantity: Decimal) {
+        self.quantity -= quantity;
+        self.filled_quantity += quantity;
+        self.status = if self.leaves_quantity() == dec!(0) {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+    }
+}
+
+// An order as sent by a client, addressed to a symbol through `MatchingEngine::submit`.
+#[derive(Debug, Clone)]
+pub struct NewOrderRequest {
+    // Needed when the symbol is listed on more than one exchange.
+    pub market: Option<Market>,
+    pub order: Order,
+}
+
+impl NewOrderRequest {
+    pub fn new(order: Order) -> NewOrderRequest {
+        NewOrderRequest {
+            market: None,
+            order,
+        }
+    }
+
+    pub fn on_market(mut self, market: Market) -> NewOrderRequest {
+        self.market = Some(market);
+        self
+    }
+}
+
+impl From<Order> for NewOrderRequest {
+    fn from(order: Order) -> Self {
+        NewOrderRequest::new(order)
+    }
 }
@@ -1,24 +1,287 @@
+use super::analytics::{Benchmarks, TradeAnalytics};
+use super::auction::{AuctionBook, AuctionIndication};
+use super::candles::CandleAggregator;
+use super::circuit_breaker::CircuitBreaker;
+use super::clock::{Clock, SystemClock, Timestamp};
+use super::error::{EngineError, RejectReason};
+use super::instrument::TickTable;
+use super::market_data::{
+    BookEvent, BookUpdate, DepthLevel, DepthSnapshot, MarketByOrder, SessionStatistics, Ticker,
+};
+use super::matching::{Fifo, MatchingAlgorithm};
 use super::order::BuyOrSell;
 use super::order::Order;
+use super::order::OrderId;
+use super::order::OrderKind;
+use super::order::OrderStatus;
+use super::order::PostOnly;
+use super::order::TimeInForce;
+use super::session::{SessionTransition, TradingSession};
+use super::trade::{MatchResult, SelfTradePrevented, SelfTradePrevention, Trade};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePosition {
+    // Number of orders which will trade before this one, 0 for the front of the queue.
+    pub position: usize,
+    pub quantity_ahead: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookMode {
+    // Incoming orders match straight away.
+    Continuous,
+    // Call auction : orders accumulate until the book is uncrossed at a single price.
+    Auction,
+    // Trading halted by the operator. Resting orders stay in the book and can be cancelled.
+    Halted(HaltPolicy),
+}
+
+// What a halted book does with new orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltPolicy {
+    Reject,
+    // Collect them for the re-opening auction.
+    Queue,
+}
 
 pub struct OrderBook {
     // HashMap : [Key : Price, Value : All the orders at that price]
     pub buy_orders: BTreeMap<Decimal, Vec<Order>>,
     pub sell_orders: BTreeMap<Decimal, Vec<Order>>,
+    // Dormant stop orders : [Key : Stop Price, Value : All the orders waiting on that price]
+    pub stop_buy_orders: BTreeMap<Decimal, Vec<Order>>,
+    pub stop_sell_orders: BTreeMap<Decimal, Vec<Order>>,
+    // Market orders collected during an auction, in arrival order.
+    auction_market_orders: Vec<Order>,
+    mode: BookMode,
+    session: TradingSession,
+    // Where every resting order lives : [Key : OrderId, Value : (Side, Price)]
+    order_index: HashMap<OrderId, (BuyOrSell, Decimal)>,
+    // Orders which never rested or have left the book, kept for lookups.
+    closed_orders: HashMap<OrderId, Order>,
+    // Ids of the closed orders, oldest first. Beyond `closed_order_limit` the oldest are forgotten.
+    closed_order_ids: VecDeque<OrderId>,
+    closed_order_limit: usize,
+    next_order_id: u64,
+    next_order_sequence: u64,
+    next_trade_sequence: u64,
+    last_trade_price: Option<Decimal>,
+    last_trade_quantity: Option<Decimal>,
+    session_statistics: SessionStatistics,
+    candles: CandleAggregator,
+    analytics: TradeAnalytics,
+    tick_table: TickTable,
+    self_trade_prevention: Option<SelfTradePrevention>,
+    matching_algorithm: Box<dyn MatchingAlgorithm>,
+    // Fraction of the best price a market order may move away from it, e.g. 0.05 for 5%.
+    market_protection: Option<Decimal>,
+    circuit_breaker: Option<CircuitBreaker>,
+    previous_close: Option<Decimal>,
+    // End of the cool-off period while halted by the circuit breaker.
+    halted_until: Option<Timestamp>,
+    // Market by order feed not yet handed out.
+    book_updates: Vec<BookUpdate>,
+    next_book_update_sequence: u64,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OrderBook {
     pub fn new() -> OrderBook {
+        OrderBook::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> OrderBook {
         OrderBook {
             buy_orders: BTreeMap::new(),
             sell_orders: BTreeMap::new(),
+            stop_buy_orders: BTreeMap::new(),
+            stop_sell_orders: BTreeMap::new(),
+            auction_market_orders: Vec::new(),
+            mode: BookMode::Continuous,
+            session: TradingSession::Continuous,
+            order_index: HashMap::new(),
+            closed_orders: HashMap::new(),
+            closed_order_ids: VecDeque::new(),
+            closed_order_limit: 100_000,
+            next_order_id: 1,
+            next_order_sequence: 1,
+            next_trade_sequence: 1,
+            last_trade_price: None,
+            last_trade_quantity: None,
+            session_statistics: SessionStatistics::default(),
+            candles: CandleAggregator::new(),
+            // Rolling benchmarks are available over the last hour by default.
+            analytics: TradeAnalytics::new(60 * 60 * 1_000),
+            tick_table: TickTable::default(),
+            market_protection: None,
+            circuit_breaker: None,
+            previous_close: None,
+            halted_until: None,
+            book_updates: Vec::new(),
+            next_book_update_sequence: 1,
+            self_trade_prevention: None,
+            matching_algorithm: Box::new(Fifo),
+            clock,
+        }
+    }
+
+    pub fn set_tick_size(&mut self, tick_size: Decimal) {
+        self.tick_table = TickTable::fixed(tick_size);
+    }
+
+    pub fn set_tick_table(&mut self, tick_table: TickTable) {
+        self.tick_table = tick_table;
+    }
+
+    pub fn set_matching_algorithm(&mut self, matching_algorithm: Box<dyn MatchingAlgorithm>) {
+        self.matching_algorithm = matching_algorithm;
+    }
+
+    pub fn set_self_trade_prevention(&mut self, mode: SelfTradePrevention) {
+        self.self_trade_prevention = Some(mode);
+    }
+
+    // How many closed orders `get_order` can still find. Older ones are evicted first.
+    pub fn set_closed_order_limit(&mut self, closed_order_limit: usize) {
+        self.closed_order_limit = closed_order_limit;
+        self.evict_closed_orders();
+    }
+
+    // Hand out every closed order, oldest first, and stop keeping them for lookups.
+    pub fn purge_closed_orders(&mut self) -> Vec<Order> {
+        let ids = std::mem::take(&mut self.closed_order_ids);
+        ids.into_iter()
+            .filter_map(|id| self.closed_orders.remove(&id))
+            .collect()
+    }
+
+    pub fn set_market_protection(&mut self, band: Decimal) {
+        self.market_protection = Some(band);
+    }
+
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: CircuitBreaker) {
+        self.circuit_breaker = Some(circuit_breaker);
+    }
+
+    pub fn set_previous_close(&mut self, previous_close: Decimal) {
+        self.previous_close = Some(previous_close);
+    }
+
+    pub fn previous_close(&self) -> Option<Decimal> {
+        self.previous_close
+    }
+
+    // (Lower, Upper) price limit enforced by the circuit breaker right now.
+    pub fn price_band(&self) -> Option<(Decimal, Decimal)> {
+        self.circuit_breaker?
+            .price_band(self.previous_close, self.last_trade_price)
+    }
+
+    pub fn last_trade_price(&self) -> Option<Decimal> {
+        self.last_trade_price
+    }
+
+    // Every order entering the book gets an engine assigned id, arrival time and sequence.
+    // Whatever else the engine owns starts afresh, callers only choose the order itself.
+    fn accept_order(&mut self, order: &mut Order) {
+        order.id = OrderId(self.next_order_id);
+        self.next_order_id += 1;
+        order.status = OrderStatus::New;
+        order.filled_quantity = dec!(0);
+        order.hidden_quantity = dec!(0);
+        order.timestamp = self.clock.now();
+        self.assign_sequence(order);
+    }
+
+    // Orders only ever get a new sequence number when they lose their time priority.
+    fn assign_sequence(&mut self, order: &mut Order) {
+        order.sequence = self.next_order_sequence;
+        self.next_order_sequence += 1;
+    }
+
+    // Where an order stands in the matching queue of its price level.
+    pub fn queue_position(&self, id: OrderId) -> Option<QueuePosition> {
+        let (side, price) = self.order_index.get(&id)?;
+        let levels = match side {
+            BuyOrSell::Buy => &self.buy_orders,
+            BuyOrSell::Sell => &self.sell_orders,
+        };
+        let orders = levels.get(price)?;
+        let order = orders.iter().find(|order| order.id == id)?;
+        // Hidden orders queue behind every displayed order at the same price.
+        let orders_ahead: Vec<&Order> = orders
+            .iter()
+            .filter(|resting| {
+                (!resting.hidden && order.hidden)
+                    || (resting.hidden == order.hidden && resting.sequence < order.sequence)
+            })
+            .collect();
+        Some(QueuePosition {
+            position: orders_ahead.len(),
+            quantity_ahead: orders_ahead.iter().map(|resting| resting.quantity).sum(),
+        })
+    }
+
+    pub fn get_order(&self, id: OrderId) -> Option<&Order> {
+        match self.order_index.get(&id) {
+            Some((side, price)) => {
+                let levels = match side {
+                    BuyOrSell::Buy => &self.buy_orders,
+                    BuyOrSell::Sell => &self.sell_orders,
+                };
+                levels
+                    .get(price)
+                    .and_then(|orders| orders.iter().find(|order| order.id == id))
+            }
+            None => self.closed_orders.get(&id).or_else(|| {
+                self.stop_buy_orders
+                    .values()
+                    .chain(self.stop_sell_orders.values())
+                    .flatten()
+                    .chain(self.auction_market_orders.iter())
+                    .find(|order| order.id == id)
+            }),
         }
     }
 
-    pub fn add_order_to_orderbook(&mut self, order: Order) {
+    pub fn add_order_to_orderbook(&mut self, mut order: Order) -> OrderId {
+        self.accept_order(&mut order);
+        self.rest_order(order)
+    }
+
+    // Puts an order the book has already accepted in its price level.
+    fn rest_order(&mut self, mut order: Order) -> OrderId {
+        // Only the peak of an iceberg order is displayed, the rest waits in reserve.
+        // Without a positive peak the whole order is displayed.
+        if let Some(display_quantity) = order.display_quantity {
+            if display_quantity > dec!(0) && order.quantity > display_quantity {
+                order.hidden_quantity += order.quantity - display_quantity;
+                order.quantity = display_quantity;
+            }
+        }
+        let order_id = order.id;
+        self.order_index
+            .insert(order_id, (order.order_type, order.price));
+        // Published once the order is in the book.
+        let added = BookEvent::OrderAdded {
+            id: order_id,
+            side: order.order_type,
+            price: order.price,
+            quantity: order.quantity,
+        };
+        let displayed = !order.hidden;
         // Check the order type whether it is a buy or sell order
         let order_price = order.price;
 
@@ -27,8 +290,10 @@ impl OrderBook {
                 // Check If the price exists in the buy_orders HashMap
                 match self.buy_orders.get_mut(&order_price) {
                     Some(orders) => {
-                        // If it exists, add the order to the existing price point
-                        orders.push(order);
+                        // If it exists, add the order to the existing price point in sequence order
+                        let position =
+                            orders.partition_point(|resting| resting.sequence < order.sequence);
+                        orders.insert(position, order);
                     }
                     None => {
                         // If it does not exist, create a new price point and add the order
@@ -40,8 +305,10 @@ impl OrderBook {
                 // Check If the price exists in the sell_orders HashMap
                 match self.sell_orders.get_mut(&order_price) {
                     Some(orders) => {
-                        // If it exists, add the order to the existing price point
-                        orders.push(order);
+                        // If it exists, add the order to the existing price point in sequence order
+                        let position =
+                            orders.partition_point(|resting| resting.sequence < order.sequence);
+                        orders.insert(position, order);
                     }
                     None => {
                         // If it does not exist, create a new price point and add the order
@@ -50,16 +317,425 @@ impl OrderBook {
                 }
             }
         }
+        if displayed {
+            self.publish(added);
+        }
+        order_id
+    }
+
+    // Take a resting order out of its price level, dropping the level once it is empty.
+    fn remove_resting_order(&mut self, id: OrderId) -> Option<Order> {
+        let (side, price) = self.order_index.remove(&id)?;
+        let levels = match side {
+            BuyOrSell::Buy => &mut self.buy_orders,
+            BuyOrSell::Sell => &mut self.sell_orders,
+        };
+        let orders = levels.get_mut(&price)?;
+        let position = orders.iter().position(|order| order.id == id)?;
+        let order = orders.remove(position);
+        if orders.is_empty() {
+            levels.remove(&price);
+        }
+        if !order.hidden {
+            self.publish(BookEvent::OrderCancelled { id, side, price });
+            self.publish_if_level_cleared(side, price);
+        }
+        Some(order)
     }
 
+    fn get_resting_order_mut(&mut self, id: OrderId) -> Option<&mut Order> {
+        let (side, price) = self.order_index.get(&id)?;
+        let levels = match side {
+            BuyOrSell::Buy => &mut self.buy_orders,
+            BuyOrSell::Sell => &mut self.sell_orders,
+        };
+        levels
+            .get_mut(price)?
+            .iter_mut()
+            .find(|order| order.id == id && !order.status.is_terminal())
+    }
+
+    pub fn cancel_order(&mut self, id: OrderId) -> Result<Order, EngineError> {
+        // Filled orders can not be cancelled anymore.
+        let order = match self.get_resting_order_mut(id) {
+            Some(_) => self.remove_resting_order(id),
+            None => self
+                .remove_stop_order(id)
+                .or_else(|| self.remove_auction_market_order(id)),
+        };
+        let mut order = order.ok_or(EngineError::UnknownOrder(id))?;
+        order.status = OrderStatus::Cancelled;
+        self.close_order(order.clone());
+        Ok(order)
+    }
+
+    // Change the open quantity of a resting order.
+    // Decreasing keeps the time priority, increasing sends the order to the back of its price level.
+    pub fn modify_quantity(
+        &mut self,
+        id: OrderId,
+        new_quantity: Decimal,
+    ) -> Result<Order, EngineError> {
+        if new_quantity <= dec!(0) {
+            return Err(RejectReason::NonPositiveQuantity.into());
+        }
+        let order = self
+            .get_resting_order_mut(id)
+            .ok_or(EngineError::UnknownOrder(id))?;
+        if new_quantity <= order.leaves_quantity() {
+            // Take the reduction out of the hidden reserve first.
+            let displayed_quantity = order.quantity;
+            order.quantity = order.quantity.min(new_quantity);
+            order.hidden_quantity = new_quantity - order.quantity;
+            let order = order.clone();
+            if !order.hidden && order.quantity != displayed_quantity {
+                self.publish(BookEvent::OrderReduced {
+                    id,
+                    side: order.order_type,
+                    price: order.price,
+                    quantity: order.quantity,
+                });
+            }
+            return Ok(order);
+        }
+        let mut order = self
+            .remove_resting_order(id)
+            .ok_or(EngineError::UnknownOrder(id))?;
+        order.quantity = new_quantity;
+        order.hidden_quantity = dec!(0);
+        self.assign_sequence(&mut order);
+        self.rest_order(order.clone());
+        Ok(order)
+    }
+
+    // Cancel/replace a resting order. A price change always loses time priority and
+    // the replaced order may trade straight away if it now crosses the book.
+    // A rejected replace leaves the original order untouched.
+    pub fn replace_order(
+        &mut self,
+        id: OrderId,
+        new_price: Decimal,
+        new_quantity: Decimal,
+    ) -> Result<MatchResult, EngineError> {
+        if new_quantity <= dec!(0) {
+            return Err(RejectReason::NonPositiveQuantity.into());
+        }
+        self.check_accepts(OrderKind::Limit)?;
+        let order = self
+            .get_resting_order_mut(id)
+            .ok_or(EngineError::UnknownOrder(id))?;
+        if order.price == new_price {
+            return self.modify_quantity(id, new_quantity).map(MatchResult::new);
+        }
+        let replaced_order = Order {
+            price: new_price,
+            ..order.clone()
+        };
+        self.check_price_band(new_price)?;
+        if replaced_order.post_only == Some(PostOnly::Reject)
+            && !self.collects_orders()
+            && self.crossing_price(&replaced_order).is_some()
+        {
+            return Err(RejectReason::WouldTakeLiquidity.into());
+        }
+        let mut order = self
+            .remove_resting_order(id)
+            .ok_or(EngineError::UnknownOrder(id))?;
+        order.price = new_price;
+        order.quantity = new_quantity;
+        order.hidden_quantity = dec!(0);
+        self.assign_sequence(&mut order);
+        Ok(self.enter_limit_order(&mut order))
+    }
+
+    pub fn mode(&self) -> BookMode {
+        self.mode
+    }
+
+    pub fn session(&self) -> TradingSession {
+        self.session
+    }
+
+    // Move the book into another trading session. Leaving a call auction uncrosses it and
+    // the end of the trading day expires every Day order.
+    // A halted book stays halted until it is resumed.
+    pub fn set_session(&mut self, session: TradingSession) -> SessionTransition {
+        let mut transition = SessionTransition {
+            expired_orders: self.expire_orders(),
+            ..SessionTransition::default()
+        };
+        if self.mode == BookMode::Auction && !session.is_call_auction() {
+            transition.trades = self.uncross_auction();
+        }
+        if session.is_call_auction() && !self.is_halted() {
+            self.start_auction();
+        }
+        let trading_day_over = |session: TradingSession| {
+            matches!(session, TradingSession::PostClose | TradingSession::Closed)
+        };
+        if !trading_day_over(session) && trading_day_over(self.session) {
+            self.session_statistics = SessionStatistics::default();
+            self.analytics.start_session(self.clock.now());
+        }
+        if trading_day_over(session) && !trading_day_over(self.session) {
+            self.analytics.end_session(self.clock.now());
+            transition.expired_orders.extend(self.expire_day_orders());
+            // The static band of the next day is based on today's close.
+            if let Some(last_trade_price) = self.last_trade_price {
+                self.previous_close = Some(last_trade_price);
+            }
+        }
+        self.session = session;
+        transition
+    }
+
+    pub fn is_halted(&self) -> bool {
+        matches!(self.mode, BookMode::Halted(_))
+    }
+
+    // Stop all trading. Any auction in progress is suspended with its orders.
+    pub fn halt(&mut self, policy: HaltPolicy) {
+        self.mode = BookMode::Halted(policy);
+    }
+
+    // Lift a halt through a re-opening auction, which uncrosses straight away unless the
+    // session is a call auction anyway.
+    pub fn resume(&mut self) -> Vec<Trade> {
+        if !self.is_halted() {
+            return Vec::new();
+        }
+        self.halted_until = None;
+        self.start_auction();
+        if self.session.is_call_auction() {
+            return Vec::new();
+        }
+        self.uncross_auction()
+    }
+
+    // Resume a book halted by its circuit breaker once the cool-off period is over.
+    pub fn end_cool_off(&mut self) -> Option<Vec<Trade>> {
+        let halted_until = self.halted_until?;
+        if self.clock.now() < halted_until {
+            return None;
+        }
+        Some(self.resume())
+    }
+
+    // Halt for the cool-off period if any of the trades reached the edge of the price band.
+    fn check_circuit_breaker(&mut self, band: Option<(Decimal, Decimal)>, trades: &[Trade]) {
+        let (circuit_breaker, (lower, upper)) = match (self.circuit_breaker, band) {
+            (Some(circuit_breaker), Some(band)) => (circuit_breaker, band),
+            _ => return,
+        };
+        if self.is_halted()
+            || !trades
+                .iter()
+                .any(|trade| trade.price <= lower || trade.price >= upper)
+        {
+            return;
+        }
+        self.halt(HaltPolicy::Queue);
+        self.halted_until = Some(self.clock.now() + circuit_breaker.cool_off);
+    }
+
+    // Cancel every resting, dormant stop and queued market order, e.g. when the instrument is delisted.
+    pub fn cancel_all_orders(&mut self) -> Vec<Order> {
+        let mut ids: Vec<OrderId> = self.order_index.keys().copied().collect();
+        ids.extend(
+            self.stop_buy_orders
+                .values()
+                .chain(self.stop_sell_orders.values())
+                .flatten()
+                .chain(self.auction_market_orders.iter())
+                .map(|order| order.id),
+        );
+        ids.sort();
+        ids.into_iter()
+            .filter_map(|id| self.cancel_order(id).ok())
+            .collect()
+    }
+
+    // Whether incoming orders only accumulate instead of matching.
+    fn collects_orders(&self) -> bool {
+        matches!(
+            self.mode,
+            BookMode::Auction | BookMode::Halted(HaltPolicy::Queue)
+        )
+    }
+
+    fn check_accepts(&self, kind: OrderKind) -> Result<(), RejectReason> {
+        if self.mode == BookMode::Halted(HaltPolicy::Reject) {
+            return Err(RejectReason::InstrumentHalted);
+        }
+        if !self.session.accepts(kind) {
+            return Err(RejectReason::NotAcceptedInSession(self.session));
+        }
+        Ok(())
+    }
+
+    // Whether an incoming order may enter the book right now.
+    fn check_order(&self, order: &Order) -> Result<(), RejectReason> {
+        self.check_accepts(order.kind)?;
+        if order
+            .display_quantity
+            .is_some_and(|display_quantity| display_quantity <= dec!(0))
+        {
+            return Err(RejectReason::NonPositiveDisplayQuantity);
+        }
+        Ok(())
+    }
+
+    // Stop continuous matching and start collecting orders for a call auction.
+    pub fn start_auction(&mut self) {
+        self.mode = BookMode::Auction;
+    }
+
+    // Equilibrium price, matched quantity and imbalance if the auction were uncrossed now.
+    pub fn indicative_auction(&self) -> Option<AuctionIndication> {
+        if self.mode != BookMode::Auction {
+            return None;
+        }
+        let levels = |levels: &BTreeMap<Decimal, Vec<Order>>| {
+            levels
+                .iter()
+                .map(|(price, orders)| {
+                    let quantity = orders.iter().map(|order| order.leaves_quantity()).sum();
+                    (*price, quantity)
+                })
+                .collect()
+        };
+        let market_quantity = |order_type: BuyOrSell| {
+            self.auction_market_orders
+                .iter()
+                .filter(|order| order.order_type == order_type)
+                .map(|order| order.quantity)
+                .sum()
+        };
+        AuctionBook {
+            bids: levels(&self.buy_orders),
+            asks: levels(&self.sell_orders),
+            market_buy_quantity: market_quantity(BuyOrSell::Buy),
+            market_sell_quantity: market_quantity(BuyOrSell::Sell),
+            reference_price: self.last_trade_price,
+        }
+        .equilibrium()
+    }
+
+    // Execute every crossing order at the equilibrium price and go back to continuous matching.
+    // Market orders left over are cancelled, limit orders left over keep resting.
+    pub fn uncross_auction(&mut self) -> Vec<Trade> {
+        if self.is_halted() {
+            return Vec::new();
+        }
+        let indication = self.indicative_auction();
+        self.mode = BookMode::Continuous;
+        let mut trades = Vec::new();
+        if let Some(indication) = indication {
+            let mut remaining = indication.matched_quantity;
+            while remaining > dec!(0) {
+                let buy_order = self.next_auction_order(BuyOrSell::Buy, indication.price);
+                let sell_order = self.next_auction_order(BuyOrSell::Sell, indication.price);
+                let (buy_order, sell_order) = match (buy_order, sell_order) {
+                    (Some(buy_order), Some(sell_order)) => (buy_order, sell_order),
+                    _ => break,
+                };
+                let quantity = buy_order.quantity.min(sell_order.quantity).min(remaining);
+                // The order which arrived last is reported as the aggressor.
+                let (aggressor, passive) = if buy_order.sequence > sell_order.sequence {
+                    (&buy_order, &sell_order)
+                } else {
+                    (&sell_order, &buy_order)
+                };
+                trades.push(self.record_trade(aggressor, passive.id, indication.price, quantity));
+                self.fill_auction_order(&buy_order, quantity);
+                self.fill_auction_order(&sell_order, quantity);
+                remaining -= quantity;
+            }
+        }
+        for mut order in std::mem::take(&mut self.auction_market_orders) {
+            order.status = OrderStatus::Cancelled;
+            self.close_order(order);
+        }
+        for activated in self.run_triggered_stop_orders() {
+            trades.extend(activated.trades);
+        }
+        trades
+    }
+
+    // Next order in auction priority : market orders first, then limit orders by price and time.
+    fn next_auction_order(&self, order_type: BuyOrSell, price: Decimal) -> Option<Order> {
+        if let Some(order) = self
+            .auction_market_orders
+            .iter()
+            .find(|order| order.order_type == order_type)
+        {
+            return Some(order.clone());
+        }
+        let (_, orders) = match order_type {
+            BuyOrSell::Buy => self
+                .buy_orders
+                .iter()
+                .next_back()
+                .filter(|(level_price, _)| **level_price >= price)?,
+            BuyOrSell::Sell => self
+                .sell_orders
+                .iter()
+                .next()
+                .filter(|(level_price, _)| **level_price <= price)?,
+        };
+        orders
+            .iter()
+            .find(|order| !order.hidden)
+            .or_else(|| orders.first())
+            .cloned()
+    }
+
+    fn fill_auction_order(&mut self, order: &Order, quantity: Decimal) {
+        if let Some(position) = self
+            .auction_market_orders
+            .iter()
+            .position(|market_order| market_order.id == order.id)
+        {
+            let market_order = &mut self.auction_market_orders[position];
+            market_order.fill(quantity);
+            if market_order.quantity == dec!(0) {
+                let market_order = self.auction_market_orders.remove(position);
+                self.close_order(market_order);
+            }
+            return;
+        }
+        if let Some(resting_order) = self.get_resting_order_mut(order.id) {
+            resting_order.fill(quantity);
+            if !resting_order.hidden {
+                self.publish(BookEvent::OrderExecuted {
+                    id: order.id,
+                    side: order.order_type,
+                    price: order.price,
+                    quantity,
+                });
+            }
+            self.purge_filled_orders(order.order_type, order.price);
+        }
+    }
+
+    // Hidden orders are left out of every price and volume query.
     pub fn best_buy_price(&self) -> Option<Decimal> {
-        // Get the maximum price from the buy_orders HashMap
-        self.buy_orders.keys().max().cloned()
+        // Get the maximum displayed price from the buy_orders HashMap
+        Self::displayed_prices(&self.buy_orders).next_back()
     }
 
     pub fn best_sell_price(&self) -> Option<Decimal> {
-        // Get the minimum price from the sell_orders HashMap
-        self.sell_orders.keys().min().cloned()
+        // Get the minimum displayed price from the sell_orders HashMap
+        Self::displayed_prices(&self.sell_orders).next()
+    }
+
+    fn displayed_prices(
+        levels: &BTreeMap<Decimal, Vec<Order>>,
+    ) -> impl DoubleEndedIterator<Item = Decimal> + '_ {
+        levels
+            .iter()
+            .filter(|(_, orders)| orders.iter().any(|order| !order.hidden))
+            .map(|(price, _)| *price)
     }
 
     pub fn market_price(&self, order_type: BuyOrSell) -> Option<Decimal> {
@@ -70,12 +746,9 @@ impl OrderBook {
     }
 
     pub fn top_n_best_buy_prices(&self) -> Option<Vec<Decimal>> {
-        let prices = self
-            .buy_orders
-            .keys()
+        let prices = Self::displayed_prices(&self.buy_orders)
             .rev()
             .take(5) // At Max 5
-            .cloned()
             .collect::<Vec<Decimal>>();
         if prices.is_empty() {
             None
@@ -85,11 +758,8 @@ impl OrderBook {
     }
 
     pub fn top_n_best_sell_prices(&self) -> Option<Vec<Decimal>> {
-        let prices = self
-            .sell_orders
-            .keys()
+        let prices = Self::displayed_prices(&self.sell_orders)
             .take(5) // At Max 5
-            .cloned()
             .collect::<Vec<Decimal>>();
         if prices.is_empty() {
             None
@@ -98,12 +768,119 @@ impl OrderBook {
         }
     }
 
+    // Up to `levels` best price levels per side. Only displayed quantity is published,
+    // so hidden orders and iceberg reserves are left out.
+    pub fn depth(&self, levels: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            bids: Self::depth_levels(self.buy_orders.iter().rev(), levels),
+            asks: Self::depth_levels(self.sell_orders.iter(), levels),
+            timestamp: self.clock.now(),
+        }
+    }
+
+    fn depth_levels<'a>(
+        price_levels: impl Iterator<Item = (&'a Decimal, &'a Vec<Order>)>,
+        levels: usize,
+    ) -> Vec<DepthLevel> {
+        price_levels
+            .filter_map(|(price, orders)| {
+                let displayed = orders.iter().filter(|order| !order.hidden);
+                let order_count = displayed.clone().count();
+                if order_count == 0 {
+                    return None;
+                }
+                Some(DepthLevel {
+                    price: *price,
+                    quantity: displayed.map(|order| order.quantity).sum(),
+                    order_count,
+                })
+            })
+            .take(levels)
+            .collect()
+    }
+
+    pub fn session_statistics(&self) -> SessionStatistics {
+        self.session_statistics
+    }
+
+    pub fn candles(&self) -> &CandleAggregator {
+        &self.candles
+    }
+
+    // Close the bars whose interval is over even though nothing traded since.
+    pub fn roll_candles(&mut self) {
+        self.candles.roll(self.clock.now());
+    }
+
+    // How long trades and mid-prices are kept for rolling benchmarks, in milliseconds.
+    pub fn set_analytics_retention(&mut self, retention: Timestamp) {
+        self.analytics.set_retention(retention);
+    }
+
+    // Benchmarks of the current trading day.
+    pub fn session_benchmarks(&self) -> Benchmarks {
+        Benchmarks {
+            vwap: self.session_statistics.vwap(),
+            twap: self.analytics.session_twap(self.clock.now()),
+            notional: self.session_statistics.turnover,
+            volume: self.session_statistics.volume,
+            trade_count: self.session_statistics.trade_count,
+        }
+    }
+
+    // Benchmarks over the last `window` milliseconds.
+    pub fn rolling_benchmarks(&self, window: Timestamp) -> Benchmarks {
+        self.analytics.rolling(self.clock.now(), window)
+    }
+
+    pub fn ticker(&self) -> Ticker {
+        let depth = self.depth(1);
+        Ticker {
+            best_bid: depth.best_bid().copied(),
+            best_ask: depth.best_ask().copied(),
+            last_trade_price: self.last_trade_price,
+            last_trade_quantity: self.last_trade_quantity,
+            statistics: self.session_statistics,
+            previous_close: self.previous_close,
+            timestamp: depth.timestamp,
+        }
+    }
+
+    // Hand out the market by order updates published since the last call.
+    pub fn drain_book_updates(&mut self) -> Vec<BookUpdate> {
+        std::mem::take(&mut self.book_updates)
+    }
+
+    // Every displayed order in time priority. Updates after `sequence` bring it up to date.
+    pub fn market_by_order(&self) -> MarketByOrder {
+        let levels = |levels: &BTreeMap<Decimal, Vec<Order>>| {
+            levels
+                .iter()
+                .map(|(price, orders)| {
+                    let orders = orders
+                        .iter()
+                        .filter(|order| !order.hidden && order.quantity > dec!(0))
+                        .map(|order| (order.id, order.quantity))
+                        .collect::<Vec<(OrderId, Decimal)>>();
+                    (*price, orders)
+                })
+                .filter(|(_, orders)| !orders.is_empty())
+                .collect()
+        };
+        MarketByOrder {
+            sequence: self.next_book_update_sequence - 1,
+            bids: levels(&self.buy_orders),
+            asks: levels(&self.sell_orders),
+        }
+    }
+
     pub fn buy_volume(&self) -> Option<Decimal> {
         // Calculate the total volume of the buy orders
         let buy_volume: Decimal = self
             .buy_orders
             .values()
             .flatten()
+            .filter(|order| !order.hidden)
             .map(|order| order.quantity)
             .sum();
         Some(buy_volume)
@@ -115,126 +892,807 @@ impl OrderBook {
             .sell_orders
             .values()
             .flatten()
+            .filter(|order| !order.hidden)
             .map(|order| order.quantity)
             .sum();
         Some(sell_volume)
     }
 
-    pub fn match_market_order(&mut self, incoming_order: &mut Order) {
-        match incoming_order.order_type {
-            BuyOrSell::Buy => {
-                let possible_prices = self.top_n_best_sell_prices();
-                match possible_prices {
-                    Some(prices) => {
-                        for price in prices {
-                            if let Some(orders_at_this_price) = self.sell_orders.get_mut(&price) {
-                                Self::execute_match(orders_at_this_price, incoming_order);
-                            }
-                            if incoming_order.quantity == dec!(0) {
-                                break;
-                            }
-                        }
-                        if incoming_order.quantity != dec!(0) {
-                            // Incoming Order was not fully executed.
-                            self.add_order_to_orderbook(incoming_order.clone());
-                        }
+    // A market order has no limit price : it walks as many levels as needed and never rests.
+    // The unfilled remainder is cancelled, or for MarketToLimit orders rests at the last traded price.
+    pub fn match_market_order(&mut self, incoming_order: &mut Order) -> MatchResult {
+        self.accept_order(incoming_order);
+        self.enter_market_order(incoming_order)
+    }
+
+    fn enter_market_order(&mut self, incoming_order: &mut Order) -> MatchResult {
+        if let Err(reason) = self.check_order(incoming_order) {
+            return self.reject_order(incoming_order, reason);
+        }
+        let band = self.price_band();
+        let mut result = self.execute_market_order(incoming_order);
+        self.activate_stop_orders(&mut result);
+        self.check_circuit_breaker(band, &result.trades);
+        result
+    }
+
+    pub fn match_limit_order(&mut self, incoming_order: &mut Order) -> MatchResult {
+        self.accept_order(incoming_order);
+        self.enter_limit_order(incoming_order)
+    }
+
+    // Replaced orders come back through here keeping their id.
+    fn enter_limit_order(&mut self, incoming_order: &mut Order) -> MatchResult {
+        if let Err(reason) = self.check_order(incoming_order) {
+            return self.reject_order(incoming_order, reason);
+        }
+        if let Err(reason) = self.check_price_band(incoming_order.price) {
+            return self.reject_order(incoming_order, reason);
+        }
+        let band = self.price_band();
+        let mut result = self.execute_limit_order(incoming_order);
+        self.activate_stop_orders(&mut result);
+        self.check_circuit_breaker(band, &result.trades);
+        result
+    }
+
+    fn check_price_band(&self, price: Decimal) -> Result<(), RejectReason> {
+        match self.price_band() {
+            Some((lower, upper)) if price < lower || price > upper => {
+                Err(RejectReason::OutsidePriceBand { lower, upper })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn reject_order(&mut self, incoming_order: &mut Order, reason: RejectReason) -> MatchResult {
+        incoming_order.status = OrderStatus::Rejected;
+        self.close_order(incoming_order.clone());
+        let mut result = MatchResult::new(incoming_order.clone());
+        result.reject_reason = Some(reason);
+        result
+    }
+
+    // Stop orders stay dormant until the last traded price reaches their stop price.
+    // An order whose stop price has already been reached is activated straight away.
+    pub fn add_stop_order(&mut self, mut order: Order) -> MatchResult {
+        self.accept_order(&mut order);
+        if let Err(reason) = self.check_order(&order) {
+            return self.reject_order(&mut order, reason);
+        }
+        // Orders without a stop price trade straight away.
+        let stop_price = match order.kind {
+            OrderKind::Stop { stop_price } | OrderKind::StopLimit { stop_price } => stop_price,
+            OrderKind::Market | OrderKind::MarketToLimit => {
+                return self.enter_market_order(&mut order)
+            }
+            OrderKind::Limit => return self.enter_limit_order(&mut order),
+        };
+        if let (OrderKind::StopLimit { .. }, Err(reason)) =
+            (order.kind, self.check_price_band(order.price))
+        {
+            return self.reject_order(&mut order, reason);
+        }
+        let stop_orders = match order.order_type {
+            BuyOrSell::Buy => &mut self.stop_buy_orders,
+            BuyOrSell::Sell => &mut self.stop_sell_orders,
+        };
+        let mut result = MatchResult::new(order.clone());
+        stop_orders.entry(stop_price).or_default().push(order);
+        let band = self.price_band();
+        self.activate_stop_orders(&mut result);
+        self.check_circuit_breaker(band, &result.trades);
+        result
+    }
+
+    // Feed every triggered stop order into the book. Their own trades can trigger further
+    // stops, so keep going until no dormant order is triggered by the last traded price.
+    fn activate_stop_orders(&mut self, result: &mut MatchResult) {
+        for activated in self.run_triggered_stop_orders() {
+            if activated.order.id == result.order.id {
+                result.order = activated.order;
+            } else {
+                result.triggered_orders.push(activated.order);
+            }
+            result.trades.extend(activated.trades);
+        }
+    }
+
+    fn run_triggered_stop_orders(&mut self) -> Vec<MatchResult> {
+        let mut activated_orders = Vec::new();
+        while let Some(mut order) = self.next_triggered_stop_order() {
+            self.assign_sequence(&mut order);
+            let activated = match order.kind {
+                // The band may have moved since the stop limit order was accepted.
+                OrderKind::StopLimit { .. } => {
+                    order.kind = OrderKind::Limit;
+                    match self.check_price_band(order.price) {
+                        Ok(()) => self.execute_limit_order(&mut order),
+                        Err(reason) => self.reject_order(&mut order, reason),
                     }
-                    None => self.add_order_to_orderbook(incoming_order.clone()),
                 }
+                _ => {
+                    order.kind = OrderKind::Market;
+                    self.execute_market_order(&mut order)
+                }
+            };
+            activated_orders.push(activated);
+        }
+        activated_orders
+    }
+
+    fn next_triggered_stop_order(&mut self) -> Option<Order> {
+        let last_trade_price = self.last_trade_price?;
+        // Buy stops trigger once the price trades at or above the stop price, lowest stop first.
+        let triggered_buy_stop = self
+            .stop_buy_orders
+            .range(..=last_trade_price)
+            .next()
+            .map(|(stop_price, _)| *stop_price);
+        // Sell stops trigger once the price trades at or below the stop price, highest stop first.
+        let triggered_sell_stop = self
+            .stop_sell_orders
+            .range(last_trade_price..)
+            .next_back()
+            .map(|(stop_price, _)| *stop_price);
+        let (stop_orders, stop_price) = match (triggered_buy_stop, triggered_sell_stop) {
+            (Some(stop_price), _) => (&mut self.stop_buy_orders, stop_price),
+            (None, Some(stop_price)) => (&mut self.stop_sell_orders, stop_price),
+            (None, None) => return None,
+        };
+        let orders = stop_orders.get_mut(&stop_price)?;
+        let order = orders.remove(0);
+        if orders.is_empty() {
+            stop_orders.remove(&stop_price);
+        }
+        Some(order)
+    }
+
+    fn remove_stop_order(&mut self, id: OrderId) -> Option<Order> {
+        for stop_orders in [&mut self.stop_buy_orders, &mut self.stop_sell_orders] {
+            let found = stop_orders.iter().find_map(|(stop_price, orders)| {
+                orders
+                    .iter()
+                    .position(|order| order.id == id)
+                    .map(|position| (*stop_price, position))
+            });
+            if let Some((stop_price, position)) = found {
+                let orders = stop_orders.get_mut(&stop_price)?;
+                let order = orders.remove(position);
+                if orders.is_empty() {
+                    stop_orders.remove(&stop_price);
+                }
+                return Some(order);
             }
-            BuyOrSell::Sell => {
-                let possible_prices = self.top_n_best_buy_prices();
-                match possible_prices {
-                    Some(prices) => {
-                        for price in prices {
-                            if let Some(orders_at_this_price) = self.buy_orders.get_mut(&price) {
-                                Self::execute_match(orders_at_this_price, incoming_order);
-                            }
-                            if incoming_order.quantity == dec!(0) {
-                                break;
-                            }
-                        }
-                        if incoming_order.quantity != dec!(0) {
-                            // Incoming Order was not fully executed.
-                            self.add_order_to_orderbook(incoming_order.clone());
-                        }
-                    }
-                    None => self.add_order_to_orderbook(incoming_order.clone()),
+        }
+        None
+    }
+
+    fn remove_auction_market_order(&mut self, id: OrderId) -> Option<Order> {
+        let position = self
+            .auction_market_orders
+            .iter()
+            .position(|order| order.id == id)?;
+        Some(self.auction_market_orders.remove(position))
+    }
+
+    fn execute_market_order(&mut self, incoming_order: &mut Order) -> MatchResult {
+        let mut result = MatchResult::new(incoming_order.clone());
+        if self.collects_orders() {
+            // Market orders wait for the uncross, ahead of every limit order.
+            if incoming_order.time_in_force.can_rest() {
+                self.auction_market_orders.push(incoming_order.clone());
+            } else {
+                incoming_order.status = OrderStatus::Cancelled;
+                self.close_order(incoming_order.clone());
+            }
+            result.order = incoming_order.clone();
+            return result;
+        }
+        let protection_price = self.market_protection_price(incoming_order.order_type);
+        if !self.passes_time_in_force_checks(incoming_order, protection_price) {
+            return MatchResult::new(incoming_order.clone());
+        }
+        self.match_against_book(incoming_order, protection_price, &mut result);
+        if incoming_order.quantity == dec!(0) || incoming_order.status.is_terminal() {
+            // Filled, or cancelled by self trade prevention.
+            self.close_order(incoming_order.clone());
+        } else {
+            match (incoming_order.kind, self.last_trade_price) {
+                (OrderKind::MarketToLimit, Some(last_trade_price))
+                    if incoming_order.time_in_force.can_rest() =>
+                {
+                    // Incoming Order was not fully executed, keep the rest as a limit order.
+                    incoming_order.kind = OrderKind::Limit;
+                    incoming_order.price = last_trade_price;
+                    self.rest_order(incoming_order.clone());
+                }
+                _ => {
+                    incoming_order.status = OrderStatus::Cancelled;
+                    self.close_order(incoming_order.clone());
                 }
             }
         }
+        result.order = incoming_order.clone();
+        result
     }
 
-    pub fn match_limit_order(&mut self, incoming_order: &mut Order) {
+    fn execute_limit_order(&mut self, incoming_order: &mut Order) -> MatchResult {
+        let mut result = MatchResult::new(incoming_order.clone());
+        if !self.passes_time_in_force_checks(incoming_order, Some(incoming_order.price)) {
+            return MatchResult::new(incoming_order.clone());
+        }
+        if self.collects_orders() {
+            // Orders only accumulate during an auction, nothing can execute immediately.
+            if incoming_order.time_in_force.can_rest() {
+                self.rest_order(incoming_order.clone());
+            } else {
+                incoming_order.status = OrderStatus::Cancelled;
+                self.close_order(incoming_order.clone());
+            }
+            result.order = incoming_order.clone();
+            return result;
+        }
+        if let (Some(post_only), Some(opposite_price)) = (
+            incoming_order.post_only,
+            self.crossing_price(incoming_order),
+        ) {
+            match post_only {
+                PostOnly::Reject => {
+                    return self.reject_order(incoming_order, RejectReason::WouldTakeLiquidity)
+                }
+                // Rest one tick away from the opposite best price instead of taking liquidity.
+                PostOnly::Slide => {
+                    let tick_size = self.tick_table.tick_size(opposite_price);
+                    incoming_order.price = match incoming_order.order_type {
+                        BuyOrSell::Buy => opposite_price - tick_size,
+                        BuyOrSell::Sell => opposite_price + tick_size,
+                    };
+                }
+            }
+        }
+        self.match_against_book(incoming_order, Some(incoming_order.price), &mut result);
+        if incoming_order.quantity == dec!(0) || incoming_order.status.is_terminal() {
+            // Filled, or cancelled by self trade prevention.
+            self.close_order(incoming_order.clone());
+        } else if incoming_order.time_in_force.can_rest() {
+            // Incoming Order was not fully executed.
+            self.rest_order(incoming_order.clone());
+        } else {
+            // Immediate or Cancel : whatever did not trade straight away is cancelled.
+            incoming_order.status = OrderStatus::Cancelled;
+            self.close_order(incoming_order.clone());
+        }
+        result.order = incoming_order.clone();
+        result
+    }
+
+    // Best opposite price, hidden orders included, if the incoming limit order would trade against it.
+    fn crossing_price(&self, incoming_order: &Order) -> Option<Decimal> {
         match incoming_order.order_type {
-            BuyOrSell::Buy => {
-                let possible_prices = self.top_n_best_sell_prices();
-                match possible_prices {
-                    Some(prices) => {
-                        for price in prices {
-                            if incoming_order.price >= price {
-                                if let Some(orders_at_this_price) = self.sell_orders.get_mut(&price)
-                                {
-                                    Self::execute_match(orders_at_this_price, incoming_order);
-                                }
-                                if incoming_order.quantity == dec!(0) {
-                                    break;
-                                }
-                            }
+            BuyOrSell::Buy => self
+                .sell_orders
+                .keys()
+                .next()
+                .filter(|price| **price <= incoming_order.price)
+                .cloned(),
+            BuyOrSell::Sell => self
+                .buy_orders
+                .keys()
+                .next_back()
+                .filter(|price| **price >= incoming_order.price)
+                .cloned(),
+        }
+    }
+
+    // Orders which are already past their expiry, or Fill or Kill orders which can not
+    // be filled completely, are closed without touching the book.
+    fn passes_time_in_force_checks(
+        &mut self,
+        incoming_order: &mut Order,
+        limit_price: Option<Decimal>,
+    ) -> bool {
+        let status = match incoming_order.time_in_force {
+            TimeInForce::GoodTillDate(expiry) if expiry <= self.clock.now() => OrderStatus::Expired,
+            TimeInForce::FillOrKill if !self.can_fill_completely(incoming_order, limit_price) => {
+                OrderStatus::Cancelled
+            }
+            _ => return true,
+        };
+        incoming_order.status = status;
+        self.close_order(incoming_order.clone());
+        false
+    }
+
+    // Whether an incoming order could trade its whole quantity within `limit_price` right now.
+    // Orders past their expiry do not count, iceberg reserves only count from the back of the
+    // level, and self trade prevention stops the incoming order at the first resting order of
+    // its own account unless only the resting order is cancelled.
+    fn can_fill_completely(&self, incoming_order: &Order, limit_price: Option<Decimal>) -> bool {
+        let order_type = incoming_order.order_type;
+        let levels: Vec<(&Decimal, &Vec<Order>)> = match order_type {
+            BuyOrSell::Buy => self.sell_orders.iter().collect(),
+            BuyOrSell::Sell => self.buy_orders.iter().rev().collect(),
+        };
+        let now = self.clock.now();
+        let mut remaining = incoming_order.quantity;
+        let crossing_levels =
+            levels
+                .into_iter()
+                .take_while(|(price, _)| match (limit_price, order_type) {
+                    (None, _) => true,
+                    (Some(limit_price), BuyOrSell::Buy) => **price <= limit_price,
+                    (Some(limit_price), BuyOrSell::Sell) => **price >= limit_price,
+                });
+        for (_, orders) in crossing_levels {
+            // Play the level out on copies of its orders : displayed orders trade first, hidden
+            // orders behind them, and consumed iceberg peaks are replenished at the back.
+            let mut queues = [false, true].map(|hidden| {
+                orders
+                    .iter()
+                    .filter(|order| order.hidden == hidden && !Self::is_past_expiry(order, now))
+                    .cloned()
+                    .collect::<Vec<Order>>()
+            });
+            while queues.iter().any(|queue| !queue.is_empty()) {
+                for queue in queues.iter_mut() {
+                    let mut replenished = Vec::new();
+                    for mut order in std::mem::take(queue) {
+                        if remaining <= dec!(0) {
+                            return true;
                         }
-                        if incoming_order.quantity != dec!(0) {
-                            // Incoming Order was not fully executed.
-                            self.add_order_to_orderbook(incoming_order.clone());
+                        let own_order = order.account_id.is_some()
+                            && order.account_id == incoming_order.account_id;
+                        match self.self_trade_prevention {
+                            Some(SelfTradePrevention::CancelOldest) if own_order => continue,
+                            Some(_) if own_order => return false,
+                            _ => {}
                         }
-                    }
-                    None => self.add_order_to_orderbook(incoming_order.clone()),
-                }
-            }
-            BuyOrSell::Sell => {
-                let possible_prices = self.top_n_best_buy_prices();
-                match possible_prices {
-                    Some(prices) => {
-                        for price in prices {
-                            if incoming_order.price <= price {
-                                if let Some(orders_at_this_price) = self.buy_orders.get_mut(&price)
-                                {
-                                    Self::execute_match(orders_at_this_price, incoming_order);
-                                }
-                                if incoming_order.quantity == dec!(0) {
-                                    break;
-                                }
+                        let quantity = remaining.min(order.quantity);
+                        remaining -= quantity;
+                        order.fill(quantity);
+                        if order.quantity > dec!(0) {
+                            queue.push(order);
+                        } else {
+                            order.replenish();
+                            if order.quantity > dec!(0) {
+                                replenished.push(order);
                             }
                         }
-                        if incoming_order.quantity != dec!(0) {
-                            // Incoming Order was not fully executed.
-                            self.add_order_to_orderbook(incoming_order.clone());
-                        }
                     }
-                    None => self.add_order_to_orderbook(incoming_order.clone()),
+                    queue.extend(replenished);
                 }
             }
         }
+        remaining <= dec!(0)
+    }
+
+    // Expire every Good Till Date order whose expiry has passed.
+    pub fn expire_orders(&mut self) -> Vec<Order> {
+        let now = self.clock.now();
+        self.expire_where(|order| Self::is_past_expiry(order, now))
     }
 
-    fn execute_match(valid_orders: &mut Vec<Order>, incoming_order: &mut Order) {
-        for order in valid_orders.iter_mut() {
-            // Partially Matched
-            if order.quantity < incoming_order.quantity {
-                incoming_order.quantity -= order.quantity;
-                order.quantity = dec!(0);
+    fn is_past_expiry(order: &Order, now: Timestamp) -> bool {
+        matches!(order.time_in_force, TimeInForce::GoodTillDate(expiry) if expiry <= now)
+    }
+
+    // Expire every Day order, called when the trading session closes.
+    pub fn expire_day_orders(&mut self) -> Vec<Order> {
+        self.expire_where(|order| order.time_in_force == TimeInForce::Day)
+    }
+
+    fn expire_where(&mut self, predicate: impl Fn(&Order) -> bool) -> Vec<Order> {
+        let expiring_ids: Vec<OrderId> = self
+            .buy_orders
+            .values()
+            .chain(self.sell_orders.values())
+            .chain(self.stop_buy_orders.values())
+            .chain(self.stop_sell_orders.values())
+            .flatten()
+            .chain(self.auction_market_orders.iter())
+            .filter(|order| predicate(order))
+            .map(|order| order.id)
+            .collect();
+        expiring_ids
+            .into_iter()
+            .filter_map(|id| self.expire_order(id))
+            .collect()
+    }
+
+    fn expire_order(&mut self, id: OrderId) -> Option<Order> {
+        let mut order = self
+            .remove_resting_order(id)
+            .or_else(|| self.remove_stop_order(id))
+            .or_else(|| self.remove_auction_market_order(id))?;
+        order.status = OrderStatus::Expired;
+        self.close_order(order.clone());
+        Some(order)
+    }
+
+    fn close_order(&mut self, order: Order) {
+        let id = order.id;
+        if self.closed_orders.insert(id, order).is_none() {
+            self.closed_order_ids.push_back(id);
+            self.evict_closed_orders();
+        }
+    }
+
+    fn evict_closed_orders(&mut self) {
+        while self.closed_order_ids.len() > self.closed_order_limit {
+            if let Some(id) = self.closed_order_ids.pop_front() {
+                self.closed_orders.remove(&id);
             }
-            // Perfectly Matched
-            else if order.quantity == incoming_order.quantity {
-                order.quantity = dec!(0);
-                incoming_order.quantity = dec!(0);
-                break;
+        }
+    }
+
+    // Worst price a market order may trade at, based on the opposite best price on arrival,
+    // hidden orders included, and never beyond the circuit breaker price band.
+    fn market_protection_price(&self, order_type: BuyOrSell) -> Option<Decimal> {
+        let protection_price = self.market_protection.and_then(|band| {
+            let reference_price = match order_type {
+                BuyOrSell::Buy => self.sell_orders.keys().next(),
+                BuyOrSell::Sell => self.buy_orders.keys().next_back(),
             }
-            // Fully Matched
-            else {
-                order.quantity -= incoming_order.quantity;
-                incoming_order.quantity = dec!(0);
+            .copied()?;
+            match order_type {
+                BuyOrSell::Buy => Some(reference_price * (dec!(1) + band)),
+                BuyOrSell::Sell => Some(reference_price * (dec!(1) - band)),
+            }
+        });
+        let band_price = self.price_band().map(|(lower, upper)| match order_type {
+            BuyOrSell::Buy => upper,
+            BuyOrSell::Sell => lower,
+        });
+        match (protection_price, band_price) {
+            (Some(protection_price), Some(band_price)) => Some(match order_type {
+                BuyOrSell::Buy => protection_price.min(band_price),
+                BuyOrSell::Sell => protection_price.max(band_price),
+            }),
+            (price, None) | (None, price) => price,
+        }
+    }
+
+    // Walk the opposite side from its best price while it is within `limit_price`.
+    fn match_against_book(
+        &mut self,
+        incoming_order: &mut Order,
+        limit_price: Option<Decimal>,
+        result: &mut MatchResult,
+    ) {
+        let opposite_side = match incoming_order.order_type {
+            BuyOrSell::Buy => BuyOrSell::Sell,
+            BuyOrSell::Sell => BuyOrSell::Buy,
+        };
+        while incoming_order.quantity != dec!(0) && !incoming_order.status.is_terminal() {
+            let best_price = match opposite_side {
+                BuyOrSell::Sell => self.sell_orders.keys().next().cloned(),
+                BuyOrSell::Buy => self.buy_orders.keys().next_back().cloned(),
+            };
+            let price = match best_price {
+                Some(price) => price,
+                None => break,
+            };
+            let crosses = match (limit_price, incoming_order.order_type) {
+                (None, _) => true,
+                (Some(limit_price), BuyOrSell::Buy) => price <= limit_price,
+                (Some(limit_price), BuyOrSell::Sell) => price >= limit_price,
+            };
+            if !crosses {
                 break;
             }
+            // Resting orders past their expiry never trade, they expire on the way instead.
+            let now = self.clock.now();
+            let levels = match opposite_side {
+                BuyOrSell::Buy => &self.buy_orders,
+                BuyOrSell::Sell => &self.sell_orders,
+            };
+            let expired_ids: Vec<OrderId> = levels
+                .get(&price)
+                .into_iter()
+                .flatten()
+                .filter(|order| Self::is_past_expiry(order, now))
+                .map(|order| order.id)
+                .collect();
+            if !expired_ids.is_empty() {
+                for id in expired_ids {
+                    result.expired_orders.extend(self.expire_order(id));
+                }
+                continue;
+            }
+            let levels = match opposite_side {
+                BuyOrSell::Buy => &mut self.buy_orders,
+                BuyOrSell::Sell => &mut self.sell_orders,
+            };
+            if let Some(orders_at_this_price) = levels.get_mut(&price) {
+                let self_trades_prevented = result.self_trades_prevented.len();
+                let fills = Self::execute_match(
+                    self.matching_algorithm.as_ref(),
+                    orders_at_this_price,
+                    incoming_order,
+                    self.self_trade_prevention,
+                    &mut result.self_trades_prevented,
+                );
+                self.publish_level_changes(
+                    opposite_side,
+                    price,
+                    &fills,
+                    &result.self_trades_prevented[self_trades_prevented..],
+                );
+                self.record_trades(incoming_order, price, fills, &mut result.trades);
+                self.purge_filled_orders(opposite_side, price);
+            }
+        }
+    }
+
+    // Move fully filled orders out of a price level and drop the level once nothing is left in it.
+    // Icebergs whose displayed peak was consumed are replenished and go to the back of the level.
+    fn purge_filled_orders(&mut self, side: BuyOrSell, price: Decimal) {
+        let levels = match side {
+            BuyOrSell::Buy => &mut self.buy_orders,
+            BuyOrSell::Sell => &mut self.sell_orders,
+        };
+        if let Some(orders) = levels.get_mut(&price) {
+            let was_displayed = orders.iter().any(|order| !order.hidden);
+            let (consumed, mut open): (Vec<Order>, Vec<Order>) = orders
+                .drain(..)
+                .partition(|order| order.quantity == dec!(0));
+            let (replenished, filled): (Vec<Order>, Vec<Order>) = consumed
+                .into_iter()
+                .partition(|order| order.hidden_quantity > dec!(0));
+            let mut replenished_orders = Vec::new();
+            for mut order in replenished {
+                order.replenish();
+                order.sequence = self.next_order_sequence;
+                self.next_order_sequence += 1;
+                if !order.hidden {
+                    replenished_orders.push((order.id, order.quantity));
+                }
+                open.push(order);
+            }
+            *orders = open;
+            if orders.is_empty() {
+                levels.remove(&price);
+            }
+            for order in filled {
+                self.order_index.remove(&order.id);
+                self.close_order(order);
+            }
+            // Replenished icebergs show up again at the back of the level, unless hidden.
+            for (id, quantity) in replenished_orders {
+                self.publish(BookEvent::OrderAdded {
+                    id,
+                    side,
+                    price,
+                    quantity,
+                });
+            }
+            if was_displayed {
+                self.publish_if_level_cleared(side, price);
+            }
+        }
+    }
+
+    // Publish what matching did to the displayed orders of one price level : executions, and
+    // the resting orders cancelled or reduced by self trade prevention.
+    fn publish_level_changes(
+        &mut self,
+        side: BuyOrSell,
+        price: Decimal,
+        fills: &[(OrderId, Decimal)],
+        self_trades_prevented: &[SelfTradePrevented],
+    ) {
+        let levels = match side {
+            BuyOrSell::Buy => &self.buy_orders,
+            BuyOrSell::Sell => &self.sell_orders,
+        };
+        let displayed_order = |id: OrderId| {
+            levels
+                .get(&price)
+                .and_then(|orders| orders.iter().find(|order| order.id == id && !order.hidden))
+                .cloned()
+        };
+        let mut events = Vec::new();
+        for (id, quantity) in fills {
+            if displayed_order(*id).is_some() {
+                events.push(BookEvent::OrderExecuted {
+                    id: *id,
+                    side,
+                    price,
+                    quantity: *quantity,
+                });
+            }
+        }
+        for prevented in self_trades_prevented {
+            let id = prevented.passive_id;
+            match displayed_order(id) {
+                Some(order) if order.status == OrderStatus::Cancelled => {
+                    events.push(BookEvent::OrderCancelled { id, side, price })
+                }
+                Some(order) if prevented.mode == SelfTradePrevention::DecrementAndCancel => events
+                    .push(BookEvent::OrderReduced {
+                        id,
+                        side,
+                        price,
+                        quantity: order.quantity,
+                    }),
+                _ => {}
+            }
+        }
+        for event in events {
+            self.publish(event);
+        }
+    }
+
+    fn publish(&mut self, event: BookEvent) {
+        self.book_updates.push(BookUpdate {
+            sequence: self.next_book_update_sequence,
+            timestamp: self.clock.now(),
+            event,
+        });
+        self.next_book_update_sequence += 1;
+        // Every change of the displayed book may move the mid-price.
+        let mid_price = match (self.best_buy_price(), self.best_sell_price()) {
+            (Some(best_buy_price), Some(best_sell_price)) => {
+                Some((best_buy_price + best_sell_price) / dec!(2))
+            }
+            _ => None,
+        };
+        self.analytics.record_mid_price(self.clock.now(), mid_price);
+    }
+
+    fn publish_if_level_cleared(&mut self, side: BuyOrSell, price: Decimal) {
+        let levels = match side {
+            BuyOrSell::Buy => &self.buy_orders,
+            BuyOrSell::Sell => &self.sell_orders,
+        };
+        let displayed = levels
+            .get(&price)
+            .is_some_and(|orders| orders.iter().any(|order| !order.hidden));
+        if !displayed {
+            self.publish(BookEvent::LevelCleared { side, price });
+        }
+    }
+
+    // Turn the fills of one price level into sequenced trade reports.
+    fn record_trades(
+        &mut self,
+        incoming_order: &Order,
+        price: Decimal,
+        fills: Vec<(OrderId, Decimal)>,
+        trades: &mut Vec<Trade>,
+    ) {
+        for (passive_id, quantity) in fills {
+            trades.push(self.record_trade(incoming_order, passive_id, price, quantity));
+        }
+    }
+
+    fn record_trade(
+        &mut self,
+        aggressor: &Order,
+        passive_id: OrderId,
+        price: Decimal,
+        quantity: Decimal,
+    ) -> Trade {
+        let trade = Trade {
+            aggressor_id: aggressor.id,
+            passive_id,
+            price,
+            quantity,
+            aggressor_side: aggressor.order_type,
+            sequence: self.next_trade_sequence,
+            timestamp: self.clock.now(),
+        };
+        self.next_trade_sequence += 1;
+        self.last_trade_price = Some(price);
+        self.last_trade_quantity = Some(quantity);
+        self.session_statistics.record_trade(price, quantity);
+        self.candles.record_trade(trade.timestamp, price, quantity);
+        self.analytics
+            .record_trade(trade.timestamp, price, quantity);
+        trade
+    }
+
+    // Returns (Resting OrderId, Matched Quantity) for every resting order that traded.
+    fn execute_match(
+        matching_algorithm: &dyn MatchingAlgorithm,
+        valid_orders: &mut [Order],
+        incoming_order: &mut Order,
+        self_trade_prevention: Option<SelfTradePrevention>,
+        self_trades_prevented: &mut Vec<SelfTradePrevented>,
+    ) -> Vec<(OrderId, Decimal)> {
+        let mut fills = Vec::new();
+        // Displayed orders trade first, hidden orders only behind them at the same price.
+        for hidden in [false, true] {
+            let queue: Vec<usize> = (0..valid_orders.len())
+                .filter(|index| valid_orders[*index].hidden == hidden)
+                .collect();
+            let mut start = 0;
+            while start < queue.len()
+                && incoming_order.quantity != dec!(0)
+                && !incoming_order.status.is_terminal()
+            {
+                // Orders ahead of the first order of the incoming order's own account are
+                // allocated together, the self trade is then resolved before moving on.
+                let end = match self_trade_prevention {
+                    Some(_) => queue[start..]
+                        .iter()
+                        .position(|index| {
+                            let order = &valid_orders[*index];
+                            order.account_id.is_some()
+                                && order.account_id == incoming_order.account_id
+                        })
+                        .map_or(queue.len(), |position| start + position),
+                    None => queue.len(),
+                };
+                let segment = &queue[start..end];
+                let allocations = {
+                    let resting_orders: Vec<&Order> =
+                        segment.iter().map(|index| &valid_orders[*index]).collect();
+                    matching_algorithm.allocate(&resting_orders, incoming_order.quantity)
+                };
+                for (index, quantity) in segment.iter().zip(allocations) {
+                    if quantity > dec!(0) {
+                        let order = &mut valid_orders[*index];
+                        order.fill(quantity);
+                        incoming_order.fill(quantity);
+                        fills.push((order.id, quantity));
+                    }
+                }
+                if end < queue.len() && incoming_order.quantity != dec!(0) {
+                    if let Some(mode) = self_trade_prevention {
+                        self_trades_prevented.push(Self::prevent_self_trade(
+                            mode,
+                            &mut valid_orders[queue[end]],
+                            incoming_order,
+                        ));
+                    }
+                }
+                start = end + 1;
+            }
+        }
+        fills
+    }
+
+    // Resolve an incoming order meeting a resting order of the same account without a trade.
+    fn prevent_self_trade(
+        mode: SelfTradePrevention,
+        resting_order: &mut Order,
+        incoming_order: &mut Order,
+    ) -> SelfTradePrevented {
+        // Quantity which would have traded between the two orders.
+        let quantity = incoming_order.quantity.min(resting_order.leaves_quantity());
+        let (cancel_incoming, cancel_resting) = match mode {
+            SelfTradePrevention::CancelNewest => (true, false),
+            SelfTradePrevention::CancelOldest => (false, true),
+            SelfTradePrevention::CancelBoth => (true, true),
+            // Both orders shrink by the smaller quantity, whichever runs out is cancelled.
+            SelfTradePrevention::DecrementAndCancel => {
+                incoming_order.quantity -= quantity;
+                resting_order.hidden_quantity -= resting_order
+                    .hidden_quantity
+                    .min(quantity - resting_order.quantity.min(quantity));
+                resting_order.quantity -= resting_order.quantity.min(quantity);
+                (
+                    incoming_order.quantity == dec!(0),
+                    resting_order.leaves_quantity() == dec!(0),
+                )
+            }
+        };
+        if cancel_incoming {
+            incoming_order.status = OrderStatus::Cancelled;
+        }
+        if cancel_resting {
+            // Purged from the level together with the filled orders.
+            resting_order.quantity = dec!(0);
+            resting_order.hidden_quantity = dec!(0);
+            resting_order.status = OrderStatus::Cancelled;
+        }
+        SelfTradePrevented {
+            aggressor_id: incoming_order.id,
+            passive_id: resting_order.id,
+            quantity,
+            mode,
         }
     }
 }
@@ -0,0 +1,196 @@
+use super::clock::Timestamp;
+use super::engine::Market;
+use super::order::{Order, OrderKind};
+use super::trade::Trade;
+
+const MILLIS_PER_MINUTE: i64 = 60_000;
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TradingSession {
+    Closed,
+    // Orders are collected for the opening call auction.
+    PreOpen,
+    // Order entry is frozen while the opening auction is about to uncross.
+    OpeningAuction,
+    Continuous,
+    // Orders are collected for the closing call auction.
+    ClosingAuction,
+    PostClose,
+}
+
+impl TradingSession {
+    // Sessions in which the book runs as a call auction instead of matching continuously.
+    pub fn is_call_auction(&self) -> bool {
+        matches!(
+            self,
+            TradingSession::PreOpen
+                | TradingSession::OpeningAuction
+                | TradingSession::ClosingAuction
+        )
+    }
+
+    pub fn accepts(&self, kind: OrderKind) -> bool {
+        match self {
+            TradingSession::Continuous => true,
+            // Auctions only take plain limit and market orders.
+            TradingSession::PreOpen | TradingSession::ClosingAuction => matches!(
+                kind,
+                OrderKind::Limit | OrderKind::Market | OrderKind::MarketToLimit
+            ),
+            TradingSession::OpeningAuction | TradingSession::PostClose | TradingSession::Closed => {
+                false
+            }
+        }
+    }
+}
+
+// What happened to a book when its trading session changed, it resumed after a halt or
+// its Good Till Date orders expired.
+#[derive(Debug, Clone, Default)]
+pub struct SessionTransition {
+    // Trades from uncrossing a call or re-opening auction.
+    pub trades: Vec<Trade>,
+    // Good Till Date orders past their expiry, and Day orders at the end of the trading day.
+    pub expired_orders: Vec<Order>,
+}
+
+// Daylight saving rules, local time moves one hour ahead while they are in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaylightSaving {
+    // From 02:00 on the second Sunday of March to 02:00 on the first Sunday of November.
+    UnitedStates,
+}
+
+impl DaylightSaving {
+    // `local_minutes` since the epoch, in local standard time.
+    fn is_in_effect(&self, local_minutes: i64) -> bool {
+        match self {
+            DaylightSaving::UnitedStates => {
+                let year = year_of(local_minutes.div_euclid(MINUTES_PER_DAY));
+                let start = nth_sunday(year, 3, 2) * MINUTES_PER_DAY + 2 * 60;
+                // 02:00 daylight time is still 01:00 standard time.
+                let end = nth_sunday(year, 11, 1) * MINUTES_PER_DAY + 60;
+                (start..end).contains(&local_minutes)
+            }
+        }
+    }
+}
+
+// Days since 1st January 1970 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Years start in March so that the leap day comes last.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn year_of(days_since_epoch: i64) -> i64 {
+    let mut year = 1970 + days_since_epoch.div_euclid(365);
+    while days_from_civil(year, 1, 1) > days_since_epoch {
+        year -= 1;
+    }
+    while days_from_civil(year + 1, 1, 1) <= days_since_epoch {
+        year += 1;
+    }
+    year
+}
+
+// Days since the epoch of the `n`th Sunday of a month.
+fn nth_sunday(year: i64, month: i64, n: i64) -> i64 {
+    let first_day = days_from_civil(year, month, 1);
+    // 1st January 1970 was a Thursday, 0 is Monday.
+    let weekday = (first_day + 3).rem_euclid(7);
+    first_day + (6 - weekday).rem_euclid(7) + 7 * (n - 1)
+}
+
+// Trading day of a market in its local time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSchedule {
+    // Local standard time of the market relative to UTC, in minutes.
+    pub utc_offset_minutes: i64,
+    pub daylight_saving: Option<DaylightSaving>,
+    // (Minute of the local day, Session starting at that minute) sorted by minute.
+    // The market is closed before the first entry.
+    pub phases: Vec<(u32, TradingSession)>,
+    pub open_on_weekends: bool,
+}
+
+impl SessionSchedule {
+    // Continuous trading around the clock, every day of the week.
+    pub fn always_open() -> SessionSchedule {
+        SessionSchedule {
+            utc_offset_minutes: 0,
+            phases: vec![(0, TradingSession::Continuous)],
+            open_on_weekends: true,
+            daylight_saving: None,
+        }
+    }
+
+    // NSE / BSE equity session in IST.
+    pub fn indian_equities() -> SessionSchedule {
+        SessionSchedule {
+            utc_offset_minutes: 5 * 60 + 30,
+            phases: vec![
+                (9 * 60, TradingSession::PreOpen),
+                (9 * 60 + 8, TradingSession::OpeningAuction),
+                (9 * 60 + 15, TradingSession::Continuous),
+                (15 * 60 + 30, TradingSession::ClosingAuction),
+                (15 * 60 + 40, TradingSession::PostClose),
+                (16 * 60, TradingSession::Closed),
+            ],
+            open_on_weekends: false,
+            daylight_saving: None,
+        }
+    }
+
+    // NASDAQ / NYSE equity session in US Eastern time, daylight saving included.
+    pub fn us_equities() -> SessionSchedule {
+        SessionSchedule {
+            utc_offset_minutes: -5 * 60,
+            phases: vec![
+                (4 * 60, TradingSession::PreOpen),
+                (9 * 60 + 28, TradingSession::OpeningAuction),
+                (9 * 60 + 30, TradingSession::Continuous),
+                (15 * 60 + 50, TradingSession::ClosingAuction),
+                (16 * 60, TradingSession::PostClose),
+                (20 * 60, TradingSession::Closed),
+            ],
+            open_on_weekends: false,
+            daylight_saving: Some(DaylightSaving::UnitedStates),
+        }
+    }
+
+    pub fn for_market(market: &Market) -> SessionSchedule {
+        match market {
+            Market::IndianMarket(_) => SessionSchedule::indian_equities(),
+            Market::USMarket(_) => SessionSchedule::us_equities(),
+            Market::CryptoMarket(_) => SessionSchedule::always_open(),
+        }
+    }
+
+    pub fn session_at(&self, timestamp: Timestamp) -> TradingSession {
+        let mut local_minutes = timestamp as i64 / MILLIS_PER_MINUTE + self.utc_offset_minutes;
+        if self
+            .daylight_saving
+            .is_some_and(|daylight_saving| daylight_saving.is_in_effect(local_minutes))
+        {
+            local_minutes += 60;
+        }
+        let days_since_epoch = local_minutes.div_euclid(MINUTES_PER_DAY);
+        let minute_of_day = local_minutes.rem_euclid(MINUTES_PER_DAY) as u32;
+        // 1st January 1970 was a Thursday, 0 is Monday.
+        let weekday = (days_since_epoch + 3).rem_euclid(7);
+        if weekday >= 5 && !self.open_on_weekends {
+            return TradingSession::Closed;
+        }
+        self.phases
+            .iter()
+            .take_while(|(start_minute, _)| *start_minute <= minute_of_day)
+            .last()
+            .map_or(TradingSession::Closed, |(_, session)| *session)
+    }
+}
@@ -0,0 +1,124 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::clock::Timestamp;
+use super::error::RejectReason;
+use super::order::{BuyOrSell, Order, OrderId, OrderStatus};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    // The incoming order which took liquidity.
+    pub aggressor_id: OrderId,
+    // The resting order which provided liquidity.
+    pub passive_id: OrderId,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub aggressor_side: BuyOrSell,
+    pub sequence: u64,
+    pub timestamp: Timestamp,
+}
+
+// What happens when an incoming order would trade against a resting order of the same account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTradePrevention {
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    // Reduce both orders by the smaller quantity and cancel whichever has nothing left.
+    DecrementAndCancel,
+}
+
+// A trade which was prevented because both sides belong to the same account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTradePrevented {
+    pub aggressor_id: OrderId,
+    pub passive_id: OrderId,
+    pub quantity: Decimal,
+    pub mode: SelfTradePrevention,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchResult {
+    // State of the incoming order once matching is over.
+    pub order: Order,
+    // Includes the trades of any stop orders triggered along the way.
+    pub trades: Vec<Trade>,
+    // Stop orders activated by these trades, in their state after matching.
+    pub triggered_orders: Vec<Order>,
+    pub self_trades_prevented: Vec<SelfTradePrevented>,
+    // Resting Good Till Date orders found past their expiry, expired instead of traded.
+    pub expired_orders: Vec<Order>,
+    // Set when the book rejected the incoming order.
+    pub reject_reason: Option<RejectReason>,
+}
+
+impl MatchResult {
+    pub fn new(order: Order) -> MatchResult {
+        MatchResult {
+            order,
+            trades: Vec::new(),
+            triggered_orders: Vec::new(),
+            self_trades_prevented: Vec::new(),
+            expired_orders: Vec::new(),
+            reject_reason: None,
+        }
+    }
+
+    // Quantity traded by the incoming order itself.
+    pub fn filled_quantity(&self) -> Decimal {
+        self.trades
+            .iter()
+            .filter(|trade| trade.aggressor_id == self.order.id)
+            .map(|trade| trade.quantity)
+            .sum()
+    }
+}
+
+// Acknowledgement sent back to the client once the engine has taken an order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderAck {
+    pub order_id: OrderId,
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub status: OrderStatus,
+    pub filled_quantity: Decimal,
+    pub leaves_quantity: Decimal,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmitResponse {
+    pub ack: OrderAck,
+    // Trades of the submitted order itself.
+    pub fills: Vec<Trade>,
+    // Everything the order caused in the book, triggered stop orders included.
+    pub result: MatchResult,
+}
+
+impl SubmitResponse {
+    pub fn new(symbol: String, result: MatchResult) -> SubmitResponse {
+        let order = &result.order;
+        // Nothing is left to trade once the order is filled, cancelled, rejected or expired.
+        let leaves_quantity = if order.status.is_terminal() {
+            dec!(0)
+        } else {
+            order.leaves_quantity()
+        };
+        let ack = OrderAck {
+            order_id: order.id,
+            client_order_id: order.client_order_id.clone(),
+            symbol,
+            status: order.status,
+            filled_quantity: order.filled_quantity,
+            leaves_quantity,
+            timestamp: order.timestamp,
+        };
+        let fills = result
+            .trades
+            .iter()
+            .filter(|trade| trade.aggressor_id == order.id)
+            .cloned()
+            .collect();
+        SubmitResponse { ack, fills, result }
+    }
+}
@@ -2,14 +2,37 @@ pub mod core_engine;
 
 #[cfg(test)]
 mod test {
-    use self::core_engine::engine::{Company, IndianExchange, Market, MatchingEngine, Sector};
+    use self::core_engine::clock::ManualClock;
+    use self::core_engine::engine::{
+        Company, CryptoExchange, IndianExchange, Market, MatchingEngine, Sector, USExchange,
+    };
 
     use super::*;
     use core_engine::{
-        order::{BuyOrSell, Order},
-        orderbook::OrderBook,
+        analytics::Benchmarks,
+        candles::{Candle, CandleInterval},
+        circuit_breaker::CircuitBreaker,
+        error::EngineError,
+        error::RejectReason,
+        instrument::{Instrument, InstrumentId, InstrumentStatus, TickTable, TradingRules},
+        market_data::{BookEvent, DepthLevel},
+        matching::{ProRata, TopOrderProRata},
+        order::{
+            BuyOrSell, NewOrderRequest, Order, OrderId, OrderKind, OrderStatus, PostOnly,
+            TimeInForce,
+        },
+        orderbook::{BookMode, HaltPolicy, OrderBook},
+        session::{SessionSchedule, TradingSession},
+        trade::SelfTradePrevention,
     };
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    // Wednesday 3rd January 2024, 10:00 IST and 10:00 EST.
+    const NSE_TRADING_HOURS: u64 = 1_704_256_200_000;
+    const NASDAQ_TRADING_HOURS: u64 = 1_704_294_000_000;
+    const MINUTE: u64 = 60_000;
 
     #[test]
     fn test_add_order_to_orderbook() {
@@ -81,7 +104,7 @@ mod test {
             Sector::Technology,
             Market::IndianMarket(IndianExchange::BSE),
         );
-        engine.list_new_company(company.clone());
+        engine.list_new_company(company.clone()).unwrap();
         assert_eq!(engine.orderbooks.len(), 1);
         match engine.get_company_orderbook(&company) {
             Some(order_book) => {
@@ -311,4 +334,1918 @@ mod test {
         assert_eq!(order_book.buy_volume(), Some(dec!(275)));
         assert_eq!(order_book.sell_volume(), Some(dec!(375)));
     }
+
+    #[test]
+    fn test_order_lifecycle_tracking() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+
+        // Every accepted order gets its own engine assigned id.
+        let sell_id_1 =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+        let sell_id_2 = order_book.add_order_to_orderbook(
+            Order::new(dec!(30), dec!(700), BuyOrSell::Sell)
+                .with_client_order_id("C-2".to_string()),
+        );
+        assert_ne!(sell_id_1, sell_id_2);
+        assert_eq!(
+            order_book.get_order(sell_id_1).unwrap().status,
+            OrderStatus::New
+        );
+        assert_eq!(
+            order_book.get_order(sell_id_2).unwrap().client_order_id,
+            Some("C-2".to_string())
+        );
+
+        // Buy 25 units : fills the first sell and part of the second one.
+        let mut incoming_order = Order::new(dec!(25), dec!(700), BuyOrSell::Buy);
+        order_book.match_limit_order(&mut incoming_order);
+        assert_eq!(incoming_order.status, OrderStatus::Filled);
+        assert_eq!(
+            order_book.get_order(incoming_order.id).unwrap().status,
+            OrderStatus::Filled
+        );
+        assert_eq!(
+            order_book.get_order(sell_id_1).unwrap().status,
+            OrderStatus::Filled
+        );
+        let sell_order_2 = order_book.get_order(sell_id_2).unwrap();
+        assert_eq!(sell_order_2.status, OrderStatus::PartiallyFilled);
+        assert_eq!(sell_order_2.quantity, dec!(15));
+        assert_eq!(sell_order_2.filled_quantity, dec!(15));
+
+        assert!(order_book.get_order(OrderId(999)).is_none());
+
+        // Closed orders are only kept up to a limit, oldest first out.
+        order_book.set_closed_order_limit(1);
+        assert!(order_book.get_order(sell_id_1).is_none());
+        assert!(order_book.get_order(incoming_order.id).is_some());
+        let purged = order_book.purge_closed_orders();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].id, incoming_order.id);
+        assert!(order_book.get_order(incoming_order.id).is_none());
+        // Resting orders are not affected.
+        assert!(order_book.get_order(sell_id_2).is_some());
+    }
+
+    #[test]
+    fn test_cancel_and_amend_orders() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+
+        let buy_id_1 =
+            order_book.add_order_to_orderbook(Order::new(dec!(35), dec!(690), BuyOrSell::Buy));
+        let buy_id_2 =
+            order_book.add_order_to_orderbook(Order::new(dec!(15), dec!(690), BuyOrSell::Buy));
+        let buy_id_3 =
+            order_book.add_order_to_orderbook(Order::new(dec!(20), dec!(685), BuyOrSell::Buy));
+        let sell_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+
+        // Cancelling removes the order and its now empty price level.
+        let cancelled = order_book.cancel_order(buy_id_3).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert_eq!(
+            order_book.get_order(buy_id_3).unwrap().status,
+            OrderStatus::Cancelled
+        );
+        assert!(!order_book.buy_orders.contains_key(&dec!(685)));
+        assert_eq!(
+            order_book.cancel_order(buy_id_3).unwrap_err(),
+            EngineError::UnknownOrder(buy_id_3)
+        );
+
+        // Decreasing the quantity keeps the time priority.
+        order_book.modify_quantity(buy_id_1, dec!(30)).unwrap();
+        let orders_at_690 = order_book.buy_orders.get(&dec!(690)).unwrap();
+        assert_eq!(orders_at_690[0].id, buy_id_1);
+        assert_eq!(orders_at_690[0].quantity, dec!(30));
+
+        // Increasing the quantity loses the time priority.
+        order_book.modify_quantity(buy_id_1, dec!(40)).unwrap();
+        let orders_at_690 = order_book.buy_orders.get(&dec!(690)).unwrap();
+        assert_eq!(orders_at_690[0].id, buy_id_2);
+        assert_eq!(orders_at_690[1].id, buy_id_1);
+        assert_eq!(order_book.buy_volume(), Some(dec!(55)));
+
+        // Replacing the price to cross the book trades straight away.
+        let replaced = order_book
+            .replace_order(buy_id_2, dec!(700), dec!(15))
+            .unwrap();
+        assert_eq!(replaced.order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(replaced.order.quantity, dec!(5));
+        assert_eq!(replaced.trades.len(), 1);
+        assert_eq!(replaced.trades[0].passive_id, sell_id);
+        assert_eq!(
+            order_book.get_order(sell_id).unwrap().status,
+            OrderStatus::Filled
+        );
+        assert_eq!(order_book.best_buy_price(), Some(dec!(700)));
+    }
+
+    #[test]
+    fn test_cancel_order_through_engine() {
+        let mut engine = MatchingEngine::with_clock(Arc::new(ManualClock::new(NSE_TRADING_HOURS)));
+        let company = Company::new(
+            "Nactore".to_string(),
+            "NACT".to_string(),
+            Sector::Technology,
+            Market::IndianMarket(IndianExchange::NSE),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        let order_id = engine
+            .get_company_orderbook(&company)
+            .unwrap()
+            .add_order_to_orderbook(Order::new(dec!(35), dec!(690), BuyOrSell::Buy));
+
+        let modified = engine
+            .replace_order(&company, order_id, dec!(691), dec!(20))
+            .unwrap();
+        assert_eq!(modified.order.price, dec!(691));
+        let cancelled = engine.cancel_order(&company, order_id).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert_eq!(
+            engine.get_company_orderbook(&company).unwrap().buy_volume(),
+            Some(dec!(0))
+        );
+    }
+
+    #[test]
+    fn test_trade_reports() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+
+        let sell_id_1 =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(1005), BuyOrSell::Sell));
+        let sell_id_2 =
+            order_book.add_order_to_orderbook(Order::new(dec!(50), dec!(1006), BuyOrSell::Sell));
+
+        // Buy 30 units at limit price of 1006 : walks two price levels.
+        let mut incoming_order = Order::new(dec!(30), dec!(1006), BuyOrSell::Buy);
+        let result = order_book.match_limit_order(&mut incoming_order);
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!(result.filled_quantity(), dec!(30));
+        assert_eq!(result.order.status, OrderStatus::Filled);
+
+        let first_trade = &result.trades[0];
+        assert_eq!(first_trade.aggressor_id, incoming_order.id);
+        assert_eq!(first_trade.passive_id, sell_id_1);
+        assert_eq!(first_trade.price, dec!(1005));
+        assert_eq!(first_trade.quantity, dec!(10));
+        assert_eq!(first_trade.aggressor_side, BuyOrSell::Buy);
+
+        let second_trade = &result.trades[1];
+        assert_eq!(second_trade.passive_id, sell_id_2);
+        assert_eq!(second_trade.price, dec!(1006));
+        assert_eq!(second_trade.quantity, dec!(20));
+        assert!(second_trade.sequence > first_trade.sequence);
+
+        // Nothing to trade against : the order rests and no trades are reported.
+        let mut resting_order = Order::new(dec!(5), dec!(1000), BuyOrSell::Buy);
+        let result = order_book.match_limit_order(&mut resting_order);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::New);
+    }
+
+    #[test]
+    fn test_filled_orders_leave_the_book() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+
+        let sell_id_1 =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(20), dec!(700), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(25), dec!(705), BuyOrSell::Sell));
+
+        // Buy 15 units : the first sell order is gone, the second one is still resting.
+        order_book.match_limit_order(&mut Order::new(dec!(15), dec!(700), BuyOrSell::Buy));
+        assert_eq!(order_book.sell_orders.get(&dec!(700)).unwrap().len(), 1);
+        assert_eq!(
+            order_book.get_order(sell_id_1).unwrap().status,
+            OrderStatus::Filled
+        );
+
+        // Buy the rest of the 700 level : the level disappears and best price moves up.
+        order_book.match_limit_order(&mut Order::new(dec!(15), dec!(700), BuyOrSell::Buy));
+        assert!(!order_book.sell_orders.contains_key(&dec!(700)));
+        assert_eq!(order_book.best_sell_price(), Some(dec!(705)));
+        assert_eq!(order_book.top_n_best_sell_prices(), Some(vec![dec!(705)]));
+        assert_eq!(order_book.sell_volume(), Some(dec!(25)));
+    }
+
+    #[test]
+    fn test_market_order_sweeps_the_book() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+
+        // Seven sell levels, more than the five best prices.
+        for (index, price) in [1005, 1006, 1007, 1008, 1009, 1010, 1011]
+            .iter()
+            .enumerate()
+        {
+            let quantity = Decimal::from(10 + index as u32);
+            order_book.add_order_to_orderbook(Order::new(
+                quantity,
+                Decimal::from(*price),
+                BuyOrSell::Sell,
+            ));
+        }
+        assert_eq!(order_book.sell_volume(), Some(dec!(91)));
+
+        // Buy 80 units at market : walks past the fifth level.
+        let result = order_book.match_market_order(&mut Order::market(dec!(80), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Filled);
+        assert_eq!(result.trades.len(), 7);
+        assert_eq!(result.trades.last().unwrap().price, dec!(1011));
+        assert_eq!(order_book.last_trade_price(), Some(dec!(1011)));
+        assert_eq!(order_book.sell_volume(), Some(dec!(11)));
+
+        // Buy 20 units at market : the unfilled remainder is cancelled instead of resting.
+        let result = order_book.match_market_order(&mut Order::market(dec!(20), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(result.order.quantity, dec!(9));
+        assert_eq!(order_book.buy_volume(), Some(dec!(0)));
+
+        // Market to limit : the remainder rests at the last traded price.
+        order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(1000), BuyOrSell::Buy));
+        let result =
+            order_book.match_market_order(&mut Order::market_to_limit(dec!(15), BuyOrSell::Sell));
+        assert_eq!(result.order.kind, OrderKind::Limit);
+        assert_eq!(result.order.price, dec!(1000));
+        assert_eq!(order_book.best_sell_price(), Some(dec!(1000)));
+        assert_eq!(order_book.sell_volume(), Some(dec!(10)));
+    }
+
+    #[test]
+    fn test_market_protection_band() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.set_market_protection(dec!(0.01));
+
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(1000), BuyOrSell::Buy));
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(995), BuyOrSell::Buy));
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(900), BuyOrSell::Buy));
+
+        // Sell 30 units at market : the 900 level is more than 1% away from the best bid.
+        let result = order_book.match_market_order(&mut Order::market(dec!(30), BuyOrSell::Sell));
+        assert_eq!(result.filled_quantity(), dec!(20));
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(order_book.best_buy_price(), Some(dec!(900)));
+
+        // Hidden orders set the reference price as well.
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(100), BuyOrSell::Sell).with_hidden(true),
+        );
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(500), BuyOrSell::Sell).with_hidden(true),
+        );
+        let result = order_book.match_market_order(&mut Order::market(dec!(20), BuyOrSell::Buy));
+        assert_eq!(result.filled_quantity(), dec!(10));
+        assert_eq!(result.trades[0].price, dec!(100));
+    }
+
+    #[test]
+    fn test_immediate_or_cancel_and_fill_or_kill() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(1005), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(20), dec!(1006), BuyOrSell::Sell));
+
+        // Fill or Kill for more than is available within the limit : nothing trades.
+        let mut fok_order = Order::new(dec!(15), dec!(1005), BuyOrSell::Buy)
+            .with_time_in_force(TimeInForce::FillOrKill);
+        let result = order_book.match_limit_order(&mut fok_order);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(order_book.sell_volume(), Some(dec!(30)));
+
+        // Fill or Kill within the available liquidity trades completely.
+        let mut fok_order = Order::new(dec!(15), dec!(1006), BuyOrSell::Buy)
+            .with_time_in_force(TimeInForce::FillOrKill);
+        let result = order_book.match_limit_order(&mut fok_order);
+        assert_eq!(result.order.status, OrderStatus::Filled);
+        assert_eq!(order_book.sell_volume(), Some(dec!(15)));
+
+        // Immediate or Cancel : the remainder does not rest.
+        let mut ioc_order = Order::new(dec!(25), dec!(1006), BuyOrSell::Buy)
+            .with_time_in_force(TimeInForce::ImmediateOrCancel);
+        let result = order_book.match_limit_order(&mut ioc_order);
+        assert_eq!(result.filled_quantity(), dec!(15));
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(order_book.buy_volume(), Some(dec!(0)));
+
+        // Fill or Kill market order against an empty side.
+        let mut fok_market_order =
+            Order::market(dec!(5), BuyOrSell::Buy).with_time_in_force(TimeInForce::FillOrKill);
+        let result = order_book.match_market_order(&mut fok_market_order);
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_day_and_good_till_date_expiry() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut order_book = OrderBook::with_clock(clock.clone());
+
+        let day_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(690), BuyOrSell::Buy).with_time_in_force(TimeInForce::Day),
+        );
+        let gtd_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(700), BuyOrSell::Sell)
+                .with_time_in_force(TimeInForce::GoodTillDate(5_000)),
+        );
+        let gtc_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(685), BuyOrSell::Buy));
+
+        // Nothing has aged out yet.
+        assert!(order_book.expire_orders().is_empty());
+
+        clock.advance(4_000);
+        let expired = order_book.expire_orders();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, gtd_id);
+        assert_eq!(
+            order_book.get_order(gtd_id).unwrap().status,
+            OrderStatus::Expired
+        );
+        assert_eq!(order_book.sell_volume(), Some(dec!(0)));
+
+        // A Good Till Date order that already expired never reaches the book.
+        let mut late_order = Order::new(dec!(10), dec!(700), BuyOrSell::Sell)
+            .with_time_in_force(TimeInForce::GoodTillDate(2_000));
+        let result = order_book.match_limit_order(&mut late_order);
+        assert_eq!(result.order.status, OrderStatus::Expired);
+
+        // A resting one past its expiry is expired instead of traded.
+        let stale_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(700), BuyOrSell::Sell)
+                .with_time_in_force(TimeInForce::GoodTillDate(8_000)),
+        );
+        clock.advance(6_000);
+        let mut buy_order = Order::new(dec!(10), dec!(700), BuyOrSell::Buy);
+        let result = order_book.match_limit_order(&mut buy_order);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.expired_orders.len(), 1);
+        assert_eq!(result.expired_orders[0].id, stale_id);
+        assert_eq!(
+            order_book.get_order(stale_id).unwrap().status,
+            OrderStatus::Expired
+        );
+        order_book.cancel_order(buy_order.id).unwrap();
+
+        // Session close.
+        let expired = order_book.expire_day_orders();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, day_id);
+        assert_eq!(
+            order_book.get_order(gtc_id).unwrap().status,
+            OrderStatus::New
+        );
+        assert_eq!(order_book.buy_volume(), Some(dec!(10)));
+    }
+
+    #[test]
+    fn test_stop_orders_cascade() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(1005), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(1006), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(1010), BuyOrSell::Sell));
+
+        // Dormant stop orders do not show up in the book.
+        let stop_result =
+            order_book.add_stop_order(Order::stop(dec!(10), dec!(1006), BuyOrSell::Buy));
+        let stop_id = stop_result.order.id;
+        let stop_limit_result = order_book.add_stop_order(Order::stop_limit(
+            dec!(5),
+            dec!(1008),
+            dec!(1010),
+            BuyOrSell::Buy,
+        ));
+        let stop_limit_id = stop_limit_result.order.id;
+        assert!(stop_result.trades.is_empty());
+        assert_eq!(order_book.buy_volume(), Some(dec!(0)));
+        assert_eq!(order_book.stop_buy_orders.len(), 2);
+
+        // Trading at 1005 does not reach any stop price.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(10), dec!(1005), BuyOrSell::Buy));
+        assert!(result.triggered_orders.is_empty());
+
+        // Trading at 1006 triggers the stop, whose fill at 1010 then triggers the stop limit.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(5), dec!(1006), BuyOrSell::Buy));
+        assert_eq!(result.filled_quantity(), dec!(5));
+        assert_eq!(result.trades.len(), 4);
+        assert_eq!(result.triggered_orders.len(), 2);
+        assert_eq!(result.triggered_orders[0].id, stop_id);
+        assert_eq!(result.triggered_orders[0].status, OrderStatus::Filled);
+        assert_eq!(result.triggered_orders[1].id, stop_limit_id);
+        assert_eq!(result.triggered_orders[1].status, OrderStatus::Filled);
+        assert!(order_book.stop_buy_orders.is_empty());
+        assert_eq!(order_book.sell_volume(), Some(dec!(0)));
+        assert_eq!(order_book.last_trade_price(), Some(dec!(1010)));
+
+        // Dormant stops can be cancelled.
+        let sell_stop = order_book.add_stop_order(Order::stop(dec!(5), dec!(900), BuyOrSell::Sell));
+        let cancelled = order_book.cancel_order(sell_stop.order.id).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert!(order_book.stop_sell_orders.is_empty());
+
+        // Orders without a stop price trade straight away, market orders never rest.
+        order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(1012), BuyOrSell::Sell));
+        let result = order_book.add_stop_order(Order::market(dec!(8), BuyOrSell::Buy));
+        assert_eq!(result.filled_quantity(), dec!(5));
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(order_book.best_buy_price(), None);
+    }
+
+    #[test]
+    fn test_iceberg_orders() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+
+        // Sell 100 units showing only 20 at a time.
+        let iceberg_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(100), dec!(700), BuyOrSell::Sell).with_display_quantity(dec!(20)),
+        );
+        let plain_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+        assert_eq!(order_book.sell_volume(), Some(dec!(30)));
+
+        // Buy 25 units : the peak is consumed, replenished and re-queued behind the plain order.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(25), dec!(700), BuyOrSell::Buy));
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!(result.trades[0].passive_id, iceberg_id);
+        assert_eq!(result.trades[0].quantity, dec!(20));
+        assert_eq!(result.trades[1].passive_id, plain_id);
+        assert_eq!(result.trades[1].quantity, dec!(5));
+
+        let orders_at_700 = order_book.sell_orders.get(&dec!(700)).unwrap();
+        assert_eq!(orders_at_700[0].id, plain_id);
+        assert_eq!(orders_at_700[1].id, iceberg_id);
+        assert_eq!(order_book.sell_volume(), Some(dec!(25)));
+
+        let iceberg_order = order_book.get_order(iceberg_id).unwrap();
+        assert_eq!(iceberg_order.quantity, dec!(20));
+        assert_eq!(iceberg_order.leaves_quantity(), dec!(80));
+        assert_eq!(iceberg_order.status, OrderStatus::PartiallyFilled);
+
+        // The hidden reserve is still available to a large incoming order.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(85), dec!(700), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Filled);
+        assert_eq!(
+            order_book.get_order(iceberg_id).unwrap().status,
+            OrderStatus::Filled
+        );
+        assert!(order_book.sell_orders.is_empty());
+
+        // An iceberg has to show something.
+        for display_quantity in [dec!(0), dec!(-5)] {
+            let result = order_book.match_limit_order(
+                &mut Order::new(dec!(10), dec!(700), BuyOrSell::Sell)
+                    .with_display_quantity(display_quantity),
+            );
+            assert_eq!(result.order.status, OrderStatus::Rejected);
+            assert_eq!(
+                result.reject_reason,
+                Some(RejectReason::NonPositiveDisplayQuantity)
+            );
+        }
+        assert_eq!(order_book.sell_volume(), Some(dec!(0)));
+        let rules = TradingRules::default();
+        let order = Order::new(dec!(10), dec!(700), BuyOrSell::Sell).with_display_quantity(dec!(0));
+        assert_eq!(
+            rules.validate(&order),
+            Err(RejectReason::NonPositiveDisplayQuantity)
+        );
+    }
+
+    #[test]
+    fn test_post_only_orders() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.set_tick_size(dec!(0.05));
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+
+        // Would take liquidity : rejected.
+        let mut post_only_order =
+            Order::new(dec!(5), dec!(700), BuyOrSell::Buy).with_post_only(PostOnly::Reject);
+        let result = order_book.match_limit_order(&mut post_only_order);
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+        assert_eq!(order_book.sell_volume(), Some(dec!(10)));
+
+        // Would take liquidity : repriced one tick below the best sell instead.
+        let mut post_only_order =
+            Order::new(dec!(5), dec!(701), BuyOrSell::Buy).with_post_only(PostOnly::Slide);
+        let result = order_book.match_limit_order(&mut post_only_order);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.price, dec!(699.95));
+        assert_eq!(order_book.best_buy_price(), Some(dec!(699.95)));
+
+        // Does not cross : rests as is.
+        let mut post_only_order =
+            Order::new(dec!(5), dec!(695), BuyOrSell::Buy).with_post_only(PostOnly::Reject);
+        let result = order_book.match_limit_order(&mut post_only_order);
+        assert_eq!(result.order.status, OrderStatus::New);
+        assert_eq!(order_book.buy_volume(), Some(dec!(10)));
+    }
+
+    #[test]
+    fn test_hidden_orders() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        let hidden_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(50), dec!(700), BuyOrSell::Sell).with_hidden(true),
+        );
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(40), dec!(699), BuyOrSell::Sell).with_hidden(true),
+        );
+        let displayed_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(20), dec!(705), BuyOrSell::Sell));
+
+        // Hidden liquidity is not displayed.
+        assert_eq!(order_book.best_sell_price(), Some(dec!(700)));
+        assert_eq!(
+            order_book.top_n_best_sell_prices(),
+            Some(vec![dec!(700), dec!(705)])
+        );
+        assert_eq!(order_book.sell_volume(), Some(dec!(30)));
+
+        // But still trades, behind displayed liquidity at the same price.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(60), dec!(700), BuyOrSell::Buy));
+        assert_eq!(result.trades.len(), 3);
+        assert_eq!(result.trades[0].price, dec!(699));
+        assert_eq!(result.trades[1].passive_id, displayed_id);
+        assert_eq!(result.trades[2].passive_id, hidden_id);
+        assert_eq!(result.trades[2].quantity, dec!(10));
+        assert_eq!(order_book.best_sell_price(), Some(dec!(705)));
+    }
+
+    #[test]
+    fn test_self_trade_prevention() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.set_self_trade_prevention(SelfTradePrevention::CancelOldest);
+        let own_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(700), BuyOrSell::Sell).with_account_id("ACC-1".to_string()),
+        );
+        let other_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(700), BuyOrSell::Sell).with_account_id("ACC-2".to_string()),
+        );
+
+        // Cancel Oldest : the resting order of the same account is cancelled, the rest trades.
+        let mut incoming_order =
+            Order::new(dec!(15), dec!(700), BuyOrSell::Buy).with_account_id("ACC-1".to_string());
+        let result = order_book.match_limit_order(&mut incoming_order);
+        assert_eq!(result.self_trades_prevented.len(), 1);
+        assert_eq!(result.self_trades_prevented[0].passive_id, own_id);
+        assert_eq!(result.self_trades_prevented[0].quantity, dec!(10));
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].passive_id, other_id);
+        assert_eq!(
+            order_book.get_order(own_id).unwrap().status,
+            OrderStatus::Cancelled
+        );
+        assert_eq!(result.order.quantity, dec!(5));
+        assert_eq!(order_book.best_buy_price(), Some(dec!(700)));
+
+        // Fill or Kill does not count on liquidity of its own account.
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(5), dec!(705), BuyOrSell::Sell).with_account_id("ACC-2".to_string()),
+        );
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(5), dec!(705), BuyOrSell::Sell).with_account_id("ACC-1".to_string()),
+        );
+        let mut fill_or_kill = Order::new(dec!(10), dec!(705), BuyOrSell::Buy)
+            .with_account_id("ACC-1".to_string())
+            .with_time_in_force(TimeInForce::FillOrKill);
+        let result = order_book.match_limit_order(&mut fill_or_kill);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(result.order.filled_quantity, dec!(0));
+        assert_eq!(order_book.sell_volume(), Some(dec!(10)));
+
+        // Nor on iceberg reserves which only come back behind its own order.
+        let mut order_book = OrderBook::new();
+        order_book.set_self_trade_prevention(SelfTradePrevention::CancelNewest);
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(20), dec!(100), BuyOrSell::Sell)
+                .with_account_id("ACC-2".to_string())
+                .with_display_quantity(dec!(2)),
+        );
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(1), dec!(100), BuyOrSell::Sell).with_account_id("ACC-1".to_string()),
+        );
+        let fill_or_kill = |account_id: &str| {
+            Order::new(dec!(8), dec!(100), BuyOrSell::Buy)
+                .with_account_id(account_id.to_string())
+                .with_time_in_force(TimeInForce::FillOrKill)
+        };
+        let result = order_book.match_limit_order(&mut fill_or_kill("ACC-1"));
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        let result = order_book.match_limit_order(&mut fill_or_kill("ACC-3"));
+        assert_eq!(result.order.status, OrderStatus::Filled);
+        assert_eq!(result.trades.len(), 5);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_decrement_and_cancel() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.set_self_trade_prevention(SelfTradePrevention::DecrementAndCancel);
+        let own_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(30), dec!(700), BuyOrSell::Sell).with_account_id("ACC-1".to_string()),
+        );
+
+        // The smaller incoming order is cancelled, the resting one shrinks without a trade.
+        let mut incoming_order =
+            Order::new(dec!(10), dec!(700), BuyOrSell::Buy).with_account_id("ACC-1".to_string());
+        let result = order_book.match_limit_order(&mut incoming_order);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(order_book.get_order(own_id).unwrap().quantity, dec!(20));
+        assert_eq!(order_book.buy_volume(), Some(dec!(0)));
+
+        // Cancel Newest : the incoming order stops at the first order of its own account.
+        order_book.set_self_trade_prevention(SelfTradePrevention::CancelNewest);
+        let mut incoming_order =
+            Order::new(dec!(10), dec!(700), BuyOrSell::Buy).with_account_id("ACC-1".to_string());
+        let result = order_book.match_limit_order(&mut incoming_order);
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(
+            result.self_trades_prevented[0].mode,
+            SelfTradePrevention::CancelNewest
+        );
+        assert_eq!(order_book.get_order(own_id).unwrap().quantity, dec!(20));
+    }
+
+    #[test]
+    fn test_price_time_priority() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut order_book = OrderBook::with_clock(clock.clone());
+
+        let first_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Buy));
+        clock.advance(5);
+        let second_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(20), dec!(700), BuyOrSell::Buy));
+        let third_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(30), dec!(700), BuyOrSell::Buy));
+
+        let first_order = order_book.get_order(first_id).unwrap();
+        let second_order = order_book.get_order(second_id).unwrap();
+        assert!(first_order.sequence < second_order.sequence);
+        assert_eq!(first_order.timestamp, 1_000);
+        assert_eq!(second_order.timestamp, 1_005);
+
+        let position = order_book.queue_position(third_id).unwrap();
+        assert_eq!(position.position, 2);
+        assert_eq!(position.quantity_ahead, dec!(30));
+
+        // Moving away and back to the same price goes to the back of the queue.
+        order_book
+            .replace_order(first_id, dec!(699), dec!(10))
+            .unwrap();
+        order_book
+            .replace_order(first_id, dec!(700), dec!(10))
+            .unwrap();
+        assert_eq!(order_book.queue_position(first_id).unwrap().position, 2);
+        assert_eq!(order_book.queue_position(second_id).unwrap().position, 0);
+
+        // Reducing quantity keeps the place in the queue.
+        order_book.modify_quantity(second_id, dec!(5)).unwrap();
+        assert_eq!(order_book.queue_position(second_id).unwrap().position, 0);
+        assert_eq!(
+            order_book.queue_position(third_id).unwrap().quantity_ahead,
+            dec!(5)
+        );
+
+        // Fills follow the queue.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(40), dec!(700), BuyOrSell::Sell));
+        let passive_ids: Vec<OrderId> =
+            result.trades.iter().map(|trade| trade.passive_id).collect();
+        assert_eq!(passive_ids, vec![second_id, third_id, first_id]);
+    }
+
+    #[test]
+    fn test_pro_rata_matching() {
+        let mut engine =
+            MatchingEngine::with_clock(Arc::new(ManualClock::new(NASDAQ_TRADING_HOURS)));
+        let company = Company::new(
+            "Nactore".to_string(),
+            "NACT".to_string(),
+            Sector::Technology,
+            Market::USMarket(USExchange::NASDAQ),
+        );
+        engine
+            .list_new_company_with_algorithm(
+                company.clone(),
+                Box::new(ProRata::new(dec!(1), dec!(2))),
+            )
+            .unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        let small_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(2), dec!(700), BuyOrSell::Sell));
+        let medium_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(30), dec!(700), BuyOrSell::Sell));
+        let large_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(68), dec!(700), BuyOrSell::Sell));
+
+        // Buy 50 units : shares of 1, 15 and 34, the first is below the minimum allocation
+        // and the unit left over goes to the oldest order.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(50), dec!(700), BuyOrSell::Buy));
+        let fills: Vec<(OrderId, Decimal)> = result
+            .trades
+            .iter()
+            .map(|trade| (trade.passive_id, trade.quantity))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (small_id, dec!(1)),
+                (medium_id, dec!(15)),
+                (large_id, dec!(34))
+            ]
+        );
+        assert_eq!(order_book.sell_volume(), Some(dec!(50)));
+
+        // When the small order is not the oldest, the share it loses goes to the oldest order.
+        let mut order_book = OrderBook::new();
+        order_book.set_matching_algorithm(Box::new(ProRata::new(dec!(1), dec!(2))));
+        let medium_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(30), dec!(700), BuyOrSell::Sell));
+        let small_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(2), dec!(700), BuyOrSell::Sell));
+        let large_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(68), dec!(700), BuyOrSell::Sell));
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(50), dec!(700), BuyOrSell::Buy));
+        let fills: Vec<(OrderId, Decimal)> = result
+            .trades
+            .iter()
+            .map(|trade| (trade.passive_id, trade.quantity))
+            .collect();
+        assert_eq!(fills, vec![(medium_id, dec!(16)), (large_id, dec!(34))]);
+        assert_eq!(
+            order_book.get_order(small_id).unwrap().status,
+            OrderStatus::New
+        );
+    }
+
+    #[test]
+    fn test_top_order_pro_rata_matching() {
+        // Initialze the new order_book
+        let mut order_book = OrderBook::new();
+        order_book.set_matching_algorithm(Box::new(TopOrderProRata::new(
+            dec!(0.4),
+            ProRata::new(dec!(1), dec!(0)),
+        )));
+        let first_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+        let second_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(30), dec!(700), BuyOrSell::Sell));
+        let third_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(60), dec!(700), BuyOrSell::Sell));
+
+        // Buy 50 units : the top order is filled first, the remaining 40 are shared pro-rata.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(50), dec!(700), BuyOrSell::Buy));
+        let fills: Vec<(OrderId, Decimal)> = result
+            .trades
+            .iter()
+            .map(|trade| (trade.passive_id, trade.quantity))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (first_id, dec!(10)),
+                (second_id, dec!(14)),
+                (third_id, dec!(26))
+            ]
+        );
+        assert_eq!(
+            order_book.get_order(first_id).unwrap().status,
+            OrderStatus::Filled
+        );
+    }
+
+    #[test]
+    fn test_call_auction_uncross() {
+        // Initialze the new order_book in a pre-open call auction.
+        let mut order_book = OrderBook::new();
+        order_book.start_auction();
+        assert_eq!(order_book.mode(), BookMode::Auction);
+
+        // Orders accumulate without matching even though they cross.
+        order_book.match_limit_order(&mut Order::new(dec!(100), dec!(105), BuyOrSell::Buy));
+        order_book.match_limit_order(&mut Order::new(dec!(50), dec!(103), BuyOrSell::Buy));
+        order_book.match_limit_order(&mut Order::new(dec!(80), dec!(101), BuyOrSell::Sell));
+        order_book.match_limit_order(&mut Order::new(dec!(60), dec!(104), BuyOrSell::Sell));
+        let result = order_book.match_market_order(&mut Order::market(dec!(20), BuyOrSell::Buy));
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::New);
+        assert_eq!(order_book.buy_volume(), Some(dec!(150)));
+
+        // 120 units can execute at both 104 and 105 with the same imbalance, the sell
+        // surplus pushes the price down to 104.
+        let indication = order_book.indicative_auction().unwrap();
+        assert_eq!(indication.price, dec!(104));
+        assert_eq!(indication.matched_quantity, dec!(120));
+        assert_eq!(indication.imbalance, dec!(20));
+        assert_eq!(indication.imbalance_side, Some(BuyOrSell::Sell));
+
+        let trades = order_book.uncross_auction();
+        assert_eq!(order_book.mode(), BookMode::Continuous);
+        assert_eq!(trades.len(), 3);
+        assert!(trades.iter().all(|trade| trade.price == dec!(104)));
+        assert_eq!(
+            trades.iter().map(|trade| trade.quantity).sum::<Decimal>(),
+            dec!(120)
+        );
+        assert_eq!(order_book.last_trade_price(), Some(dec!(104)));
+
+        // What did not cross keeps resting for continuous trading.
+        assert_eq!(order_book.best_buy_price(), Some(dec!(103)));
+        assert_eq!(order_book.best_sell_price(), Some(dec!(104)));
+        assert_eq!(order_book.sell_volume(), Some(dec!(20)));
+        assert_eq!(order_book.indicative_auction(), None);
+    }
+
+    #[test]
+    fn test_trading_day_sessions() {
+        // 08:55 IST : the market is still closed.
+        let clock = Arc::new(ManualClock::new(NSE_TRADING_HOURS - 65 * MINUTE));
+        let mut engine = MatchingEngine::with_clock(clock.clone());
+        let company = Company::new(
+            "Nactore".to_string(),
+            "NACT".to_string(),
+            Sector::Technology,
+            Market::IndianMarket(IndianExchange::NSE),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.session(), TradingSession::Closed);
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(10), dec!(700), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+
+        // 09:00 IST : pre-open, crossing orders are collected for the opening auction.
+        clock.advance(5 * MINUTE);
+        assert_eq!(engine.update_sessions().len(), 1);
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.session(), TradingSession::PreOpen);
+        order_book.match_limit_order(&mut Order::new(dec!(10), dec!(701), BuyOrSell::Buy));
+        order_book.match_limit_order(
+            &mut Order::new(dec!(15), dec!(699), BuyOrSell::Sell)
+                .with_time_in_force(TimeInForce::Day),
+        );
+        let result = order_book.add_stop_order(Order::stop(dec!(5), dec!(710), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+
+        // 09:08 IST : order entry is frozen.
+        clock.advance(8 * MINUTE);
+        engine.update_sessions();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.session(), TradingSession::OpeningAuction);
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(10), dec!(700), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+
+        // 09:15 IST : continuous trading starts with the uncross of the opening auction.
+        clock.advance(7 * MINUTE);
+        let transitions = engine.update_sessions();
+        assert_eq!(transitions[0].1.trades.len(), 1);
+        assert_eq!(transitions[0].1.trades[0].quantity, dec!(10));
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.session(), TradingSession::Continuous);
+        assert_eq!(order_book.sell_volume(), Some(dec!(5)));
+        let gtd_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(10), dec!(690), BuyOrSell::Buy)
+                .with_time_in_force(TimeInForce::GoodTillDate(NSE_TRADING_HOURS - 44 * MINUTE)),
+        );
+
+        // 09:17 IST : the Good Till Date order has expired.
+        clock.advance(2 * MINUTE);
+        let transitions = engine.update_sessions();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].1.expired_orders[0].id, gtd_id);
+
+        // 15:40 IST : post close, the remaining Day order expires.
+        clock.advance(6 * 60 * MINUTE + 23 * MINUTE);
+        let transitions = engine.update_sessions();
+        assert_eq!(transitions[0].1.expired_orders.len(), 1);
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.session(), TradingSession::PostClose);
+        assert_eq!(order_book.sell_volume(), Some(dec!(0)));
+    }
+
+    #[test]
+    fn test_session_schedules() {
+        // Saturday 6th January 2024, 10:00 IST.
+        let saturday = NSE_TRADING_HOURS + 3 * 24 * 60 * MINUTE;
+        let nse = Market::IndianMarket(IndianExchange::NSE);
+        let binance = Market::CryptoMarket(CryptoExchange::Binance);
+        let mut engine = MatchingEngine::with_clock(Arc::new(ManualClock::new(saturday)));
+        assert_eq!(engine.current_session(&nse), TradingSession::Closed);
+        assert_eq!(engine.current_session(&binance), TradingSession::Continuous);
+
+        // Custom schedules replace the default one of a market.
+        engine.set_session_schedule(nse.clone(), SessionSchedule::always_open());
+        assert_eq!(engine.current_session(&nse), TradingSession::Continuous);
+
+        let nasdaq = SessionSchedule::us_equities();
+        assert_eq!(
+            nasdaq.session_at(NASDAQ_TRADING_HOURS),
+            TradingSession::Continuous
+        );
+        assert_eq!(
+            nasdaq.session_at(NASDAQ_TRADING_HOURS - 60 * MINUTE),
+            TradingSession::PreOpen
+        );
+        assert_eq!(
+            nasdaq.session_at(NASDAQ_TRADING_HOURS + 6 * 60 * MINUTE - 5 * MINUTE),
+            TradingSession::ClosingAuction
+        );
+
+        // 13:30 UTC is 08:30 EST on Friday 8th March 2024, but 09:30 EDT from Sunday 10th.
+        let friday = NASDAQ_TRADING_HOURS + 65 * 24 * 60 * MINUTE - 90 * MINUTE;
+        assert_eq!(nasdaq.session_at(friday), TradingSession::PreOpen);
+        let monday = friday + 3 * 24 * 60 * MINUTE;
+        assert_eq!(nasdaq.session_at(monday), TradingSession::Continuous);
+        // Back to standard time from Sunday 3rd November 2024.
+        let november = friday + 242 * 24 * 60 * MINUTE;
+        assert_eq!(nasdaq.session_at(november), TradingSession::PreOpen);
+    }
+
+    #[test]
+    fn test_trading_halts_and_delisting() {
+        let mut engine = MatchingEngine::new();
+        let company = Company::new(
+            "Bitcoin".to_string(),
+            "BTC".to_string(),
+            Sector::Finance,
+            Market::CryptoMarket(CryptoExchange::Binance),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        order_book.match_limit_order(&mut Order::new(dec!(10), dec!(100), BuyOrSell::Buy));
+        order_book.match_limit_order(&mut Order::new(dec!(5), dec!(102), BuyOrSell::Sell));
+
+        // New orders are rejected while resting orders stay in the book.
+        engine.halt_company(&company, HaltPolicy::Reject).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.mode(), BookMode::Halted(HaltPolicy::Reject));
+        let result = order_book.match_market_order(&mut Order::market(dec!(1), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+        assert_eq!(order_book.buy_volume(), Some(dec!(10)));
+
+        // Queued orders wait for the re-opening auction.
+        engine.halt_company(&company, HaltPolicy::Queue).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(4), dec!(99), BuyOrSell::Sell));
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.status, OrderStatus::New);
+
+        let trades = engine.resume_company(&company).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, dec!(100));
+        assert_eq!(trades[0].quantity, dec!(4));
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.mode(), BookMode::Continuous);
+
+        // Delisting cancels every open order and removes the book.
+        let cancelled = engine.delist_company(&company).unwrap();
+        assert_eq!(cancelled.len(), 2);
+        assert!(cancelled
+            .iter()
+            .all(|order| order.status == OrderStatus::Cancelled));
+        assert!(engine.get_company_orderbook(&company).is_none());
+        assert_eq!(
+            engine
+                .halt_company(&company, HaltPolicy::Reject)
+                .unwrap_err(),
+            EngineError::UnknownCompany
+        );
+    }
+
+    #[test]
+    fn test_circuit_breaker_halts_and_cools_off() {
+        let clock = Arc::new(ManualClock::new(NSE_TRADING_HOURS));
+        let mut engine = MatchingEngine::with_clock(clock.clone());
+        let company = Company::new(
+            "Ethereum".to_string(),
+            "ETH".to_string(),
+            Sector::Finance,
+            Market::CryptoMarket(CryptoExchange::Coinbase),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        order_book.set_previous_close(dec!(100));
+        order_book
+            .set_circuit_breaker(CircuitBreaker::new(5 * MINUTE).with_static_band(dec!(0.10)));
+        assert_eq!(order_book.price_band(), Some((dec!(90), dec!(110))));
+
+        // Orders outside the band are rejected.
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(5), dec!(111), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+
+        // So are replaces, which leave the original order untouched.
+        let bid_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(5), dec!(100), BuyOrSell::Buy).with_post_only(PostOnly::Reject),
+        );
+        assert_eq!(
+            order_book
+                .replace_order(bid_id, dec!(200), dec!(5))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::OutsidePriceBand {
+                lower: dec!(90),
+                upper: dec!(110)
+            })
+        );
+        let ask_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(105), BuyOrSell::Sell));
+        assert_eq!(
+            order_book
+                .replace_order(bid_id, dec!(105), dec!(5))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::WouldTakeLiquidity)
+        );
+        assert_eq!(
+            order_book.get_order(bid_id).unwrap().status,
+            OrderStatus::New
+        );
+        assert_eq!(order_book.buy_volume(), Some(dec!(5)));
+        order_book.cancel_order(bid_id).unwrap();
+        order_book.cancel_order(ask_id).unwrap();
+
+        // Trading at the upper circuit halts the book, new orders wait for the re-opening.
+        order_book.match_limit_order(&mut Order::new(dec!(10), dec!(110), BuyOrSell::Sell));
+        let result = engine
+            .place_order(&company, Order::new(dec!(4), dec!(110), BuyOrSell::Buy))
+            .unwrap();
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(
+            engine.instrument(&company).unwrap().status,
+            InstrumentStatus::Halted
+        );
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.mode(), BookMode::Halted(HaltPolicy::Queue));
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(3), dec!(110), BuyOrSell::Buy));
+        assert!(result.trades.is_empty());
+
+        clock.advance(4 * MINUTE);
+        assert!(engine.update_sessions().is_empty());
+        clock.advance(MINUTE);
+        let transitions = engine.update_sessions();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].1.trades[0].quantity, dec!(3));
+        assert_eq!(
+            engine.instrument(&company).unwrap().status,
+            InstrumentStatus::Active
+        );
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.mode(), BookMode::Continuous);
+        assert_eq!(order_book.sell_volume(), Some(dec!(3)));
+    }
+
+    #[test]
+    fn test_dynamic_price_band() {
+        let mut order_book = OrderBook::new();
+        order_book.set_circuit_breaker(CircuitBreaker::new(0).with_dynamic_band(dec!(0.05)));
+        // No reference price yet, so no band and the 106 offer is accepted.
+        assert_eq!(order_book.price_band(), None);
+        order_book.match_limit_order(&mut Order::new(dec!(5), dec!(103), BuyOrSell::Sell));
+        order_book.match_limit_order(&mut Order::new(dec!(5), dec!(104), BuyOrSell::Sell));
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(5), dec!(106), BuyOrSell::Sell));
+        assert_eq!(result.order.status, OrderStatus::New);
+        order_book.match_limit_order(&mut Order::new(dec!(1), dec!(100), BuyOrSell::Sell));
+        order_book.match_limit_order(&mut Order::new(dec!(1), dec!(100), BuyOrSell::Buy));
+        assert_eq!(order_book.price_band(), Some((dec!(95), dec!(105))));
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(5), dec!(106), BuyOrSell::Sell));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+
+        // Market orders stop at the edge of the band, short of the resting 106 offer.
+        let result = order_book.match_market_order(&mut Order::market(dec!(20), BuyOrSell::Buy));
+        assert_eq!(result.filled_quantity(), dec!(10));
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(order_book.mode(), BookMode::Continuous);
+        assert_eq!(order_book.best_sell_price(), Some(dec!(106)));
+
+        // The band follows the last traded price.
+        assert_eq!(order_book.price_band(), Some((dec!(98.80), dec!(109.20))));
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(5), dec!(110), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+
+        // Stop limit prices are held to the band on entry and again once triggered.
+        let result = order_book.add_stop_order(Order::stop_limit(
+            dec!(5),
+            dec!(105),
+            dec!(150),
+            BuyOrSell::Buy,
+        ));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+        let result = order_book.add_stop_order(Order::stop_limit(
+            dec!(5),
+            dec!(105),
+            dec!(109),
+            BuyOrSell::Buy,
+        ));
+        assert_eq!(result.order.status, OrderStatus::New);
+        order_book.set_circuit_breaker(CircuitBreaker::new(0).with_dynamic_band(dec!(0.02)));
+        order_book.match_limit_order(&mut Order::new(dec!(1), dec!(105), BuyOrSell::Sell));
+        let result =
+            order_book.match_limit_order(&mut Order::new(dec!(1), dec!(105), BuyOrSell::Buy));
+        assert_eq!(result.triggered_orders.len(), 1);
+        assert_eq!(result.triggered_orders[0].status, OrderStatus::Rejected);
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(order_book.best_sell_price(), Some(dec!(106)));
+    }
+
+    #[test]
+    fn test_tick_and_lot_size_validation() {
+        let mut engine = MatchingEngine::new();
+        let company = Company::new(
+            "Solana".to_string(),
+            "SOL".to_string(),
+            Sector::Technology,
+            Market::CryptoMarket(CryptoExchange::WazirX),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        let tick_table = TickTable::new(vec![
+            (dec!(0), dec!(0.01)),
+            (dec!(250), dec!(0.05)),
+            (dec!(1000), dec!(0.10)),
+        ]);
+        let rules = TradingRules::new(tick_table)
+            .with_lot_size(dec!(5))
+            .with_minimum_quantity(dec!(10))
+            .with_maximum_quantity(dec!(1000));
+        engine.set_trading_rules(&company, rules).unwrap();
+
+        let rejections = [
+            (
+                Order::new(dec!(10), dec!(100.005), BuyOrSell::Buy),
+                RejectReason::PriceNotOnTick {
+                    tick_size: dec!(0.01),
+                },
+            ),
+            (
+                Order::new(dec!(10), dec!(300.02), BuyOrSell::Buy),
+                RejectReason::PriceNotOnTick {
+                    tick_size: dec!(0.05),
+                },
+            ),
+            (
+                Order::new(dec!(12), dec!(100), BuyOrSell::Buy),
+                RejectReason::QuantityNotInLots { lot_size: dec!(5) },
+            ),
+            (
+                Order::new(dec!(5), dec!(100), BuyOrSell::Buy),
+                RejectReason::BelowMinimumQuantity {
+                    minimum_quantity: dec!(10),
+                },
+            ),
+            (
+                Order::market(dec!(2000), BuyOrSell::Sell),
+                RejectReason::AboveMaximumQuantity {
+                    maximum_quantity: dec!(1000),
+                },
+            ),
+            (
+                Order::new(dec!(-10), dec!(100), BuyOrSell::Buy),
+                RejectReason::NonPositiveQuantity,
+            ),
+            (
+                Order::new(dec!(10), dec!(0), BuyOrSell::Buy),
+                RejectReason::NonPositivePrice,
+            ),
+            (
+                Order::stop(dec!(10), dec!(1000.05), BuyOrSell::Buy),
+                RejectReason::PriceNotOnTick {
+                    tick_size: dec!(0.10),
+                },
+            ),
+        ];
+        for (order, reason) in rejections {
+            assert_eq!(
+                engine.place_order(&company, order).unwrap_err(),
+                EngineError::Rejected(reason)
+            );
+        }
+        // Nothing reached the book.
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.best_buy_price(), None);
+
+        let result = engine
+            .place_order(&company, Order::new(dec!(10), dec!(300.05), BuyOrSell::Buy))
+            .unwrap();
+        assert_eq!(result.order.status, OrderStatus::New);
+        let result = engine
+            .place_order(&company, Order::market(dec!(15), BuyOrSell::Sell))
+            .unwrap();
+        assert_eq!(result.filled_quantity(), dec!(10));
+
+        // Amendments follow the same rules.
+        let id = engine
+            .place_order(&company, Order::new(dec!(10), dec!(100), BuyOrSell::Buy))
+            .unwrap()
+            .order
+            .id;
+        assert_eq!(
+            engine
+                .replace_order(&company, id, dec!(100.005), dec!(10))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::PriceNotOnTick {
+                tick_size: dec!(0.01)
+            })
+        );
+        assert_eq!(
+            engine
+                .replace_order(&company, id, dec!(101), dec!(7))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::QuantityNotInLots { lot_size: dec!(5) })
+        );
+        assert_eq!(
+            engine
+                .modify_quantity(&company, id, dec!(5000))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::AboveMaximumQuantity {
+                maximum_quantity: dec!(1000)
+            })
+        );
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(order_book.get_order(id).unwrap().quantity, dec!(10));
+        assert_eq!(order_book.best_buy_price(), Some(dec!(100)));
+        assert!(engine.modify_quantity(&company, id, dec!(20)).is_ok());
+
+        let rules = TradingRules::default().with_lot_size(dec!(0));
+        assert_eq!(
+            rules.validate(&Order::new(dec!(7), dec!(100), BuyOrSell::Buy)),
+            Ok(())
+        );
+
+        let unknown = Company::new(
+            "Unknown".to_string(),
+            "UNKN".to_string(),
+            Sector::Technology,
+            Market::CryptoMarket(CryptoExchange::WazirX),
+        );
+        let order = Order::new(dec!(10), dec!(100), BuyOrSell::Buy);
+        assert_eq!(
+            engine.place_order(&unknown, order).unwrap_err(),
+            EngineError::UnknownCompany
+        );
+    }
+
+    #[test]
+    fn test_engine_errors_and_reject_reasons() {
+        let mut engine = MatchingEngine::new();
+        let company = Company::new(
+            "Cardano".to_string(),
+            "ADA".to_string(),
+            Sector::Technology,
+            Market::CryptoMarket(CryptoExchange::CoinDCX),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        assert_eq!(
+            engine.list_new_company(company.clone()).unwrap_err(),
+            EngineError::AlreadyListed
+        );
+        assert_eq!(
+            engine.cancel_order(&company, OrderId(42)).unwrap_err(),
+            EngineError::UnknownOrder(OrderId(42))
+        );
+        assert_eq!(
+            engine.resume_company(&company).unwrap_err(),
+            EngineError::NotHalted
+        );
+
+        let result = engine
+            .place_order(&company, Order::new(dec!(10), dec!(100), BuyOrSell::Sell))
+            .unwrap();
+        assert_eq!(
+            engine
+                .modify_quantity(&company, result.order.id, dec!(0))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::NonPositiveQuantity)
+        );
+
+        // Rejections by the book carry their reason as well.
+        let order =
+            Order::new(dec!(10), dec!(100), BuyOrSell::Buy).with_post_only(PostOnly::Reject);
+        assert_eq!(
+            engine.place_order(&company, order).unwrap_err(),
+            EngineError::Rejected(RejectReason::WouldTakeLiquidity)
+        );
+        let order = Order::new(dec!(10), dec!(95), BuyOrSell::Buy).with_post_only(PostOnly::Reject);
+        let id = engine.place_order(&company, order).unwrap().order.id;
+        assert_eq!(
+            engine
+                .replace_order(&company, id, dec!(100), dec!(10))
+                .unwrap_err(),
+            EngineError::Rejected(RejectReason::WouldTakeLiquidity)
+        );
+        engine.halt_company(&company, HaltPolicy::Reject).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        let result = order_book.match_market_order(&mut Order::market(dec!(1), BuyOrSell::Buy));
+        assert_eq!(result.order.status, OrderStatus::Rejected);
+        assert_eq!(result.reject_reason, Some(RejectReason::InstrumentHalted));
+        assert_eq!(
+            engine
+                .replace_order(&company, OrderId(1), dec!(101), dec!(10))
+                .unwrap_err()
+                .to_string(),
+            "order rejected : instrument is halted"
+        );
+    }
+
+    #[test]
+    fn test_submit_orders_by_symbol() {
+        let mut engine = MatchingEngine::with_clock(Arc::new(ManualClock::new(NSE_TRADING_HOURS)));
+        let nse = Market::IndianMarket(IndianExchange::NSE);
+        let bse = Market::IndianMarket(IndianExchange::BSE);
+        for market in [nse.clone(), bse.clone()] {
+            let company = Company::new(
+                "Reliance Industries".to_string(),
+                "RELIANCE".to_string(),
+                Sector::Energy,
+                market,
+            );
+            engine.list_new_company(company).unwrap();
+        }
+
+        // The symbol trades on both exchanges, so the request has to pick one.
+        let order = Order::new(dec!(10), dec!(2500), BuyOrSell::Sell);
+        assert_eq!(
+            engine.submit("RELIANCE", order.clone().into()).unwrap_err(),
+            EngineError::AmbiguousSymbol
+        );
+        assert_eq!(
+            engine.submit("TCS", order.clone().into()).unwrap_err(),
+            EngineError::UnknownCompany
+        );
+        let response = engine
+            .submit(
+                "RELIANCE",
+                NewOrderRequest::new(order).on_market(nse.clone()),
+            )
+            .unwrap();
+        assert_eq!(response.ack.symbol, "RELIANCE");
+        assert_eq!(response.ack.status, OrderStatus::New);
+        assert_eq!(response.ack.leaves_quantity, dec!(10));
+        assert!(response.fills.is_empty());
+
+        // Market orders take the market path and report their fills.
+        let order =
+            Order::market(dec!(4), BuyOrSell::Buy).with_client_order_id("client-1".to_string());
+        let response = engine
+            .submit(
+                "RELIANCE",
+                NewOrderRequest::new(order).on_market(nse.clone()),
+            )
+            .unwrap();
+        assert_eq!(response.ack.client_order_id, Some("client-1".to_string()));
+        assert_eq!(response.ack.status, OrderStatus::Filled);
+        assert_eq!(response.ack.filled_quantity, dec!(4));
+        assert_eq!(response.fills.len(), 1);
+        assert_eq!(response.fills[0].price, dec!(2500));
+
+        // Nothing rests on the other exchange.
+        let order = Order::market(dec!(4), BuyOrSell::Buy);
+        let response = engine
+            .submit("RELIANCE", NewOrderRequest::new(order).on_market(bse))
+            .unwrap();
+        assert_eq!(response.ack.status, OrderStatus::Cancelled);
+        assert_eq!(response.ack.leaves_quantity, dec!(0));
+        assert!(response.fills.is_empty());
+    }
+
+    #[test]
+    fn test_engine_assigns_order_fields() {
+        let mut engine = MatchingEngine::with_clock(Arc::new(ManualClock::new(NSE_TRADING_HOURS)));
+        let company = Company::new(
+            "Reliance Industries".to_string(),
+            "RELIANCE".to_string(),
+            Sector::Energy,
+            Market::IndianMarket(IndianExchange::NSE),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+
+        let first_id = engine
+            .submit(
+                "RELIANCE",
+                Order::new(dec!(10), dec!(2500), BuyOrSell::Buy).into(),
+            )
+            .unwrap()
+            .ack
+            .order_id;
+        // Whatever the client puts in the fields owned by the engine is ignored.
+        let order = Order {
+            id: first_id,
+            status: OrderStatus::Filled,
+            filled_quantity: dec!(10),
+            hidden_quantity: dec!(100),
+            sequence: 0,
+            timestamp: 1,
+            ..Order::new(dec!(10), dec!(2500), BuyOrSell::Buy)
+        };
+        let response = engine.submit("RELIANCE", order.into()).unwrap();
+        let second_id = response.ack.order_id;
+        assert_ne!(second_id, first_id);
+        assert_eq!(response.ack.status, OrderStatus::New);
+        assert_eq!(response.ack.leaves_quantity, dec!(10));
+
+        let orderbook = engine.get_company_orderbook(&company).unwrap();
+        assert_eq!(orderbook.get_order(first_id).unwrap().quantity, dec!(10));
+        let second_order = orderbook.get_order(second_id).unwrap();
+        assert_eq!(second_order.filled_quantity, dec!(0));
+        assert_eq!(second_order.hidden_quantity, dec!(0));
+        assert_eq!(second_order.timestamp, NSE_TRADING_HOURS);
+        // The later order still queues behind the earlier one.
+        assert_eq!(orderbook.queue_position(second_id).unwrap().position, 1);
+        let result =
+            orderbook.match_limit_order(&mut Order::new(dec!(10), dec!(2500), BuyOrSell::Sell));
+        assert_eq!(result.trades[0].passive_id, first_id);
+    }
+
+    #[test]
+    fn test_instrument_registry() {
+        let mut engine = MatchingEngine::with_clock(Arc::new(ManualClock::new(NSE_TRADING_HOURS)));
+        let nse = Market::IndianMarket(IndianExchange::NSE);
+        let bse = Market::IndianMarket(IndianExchange::BSE);
+        let company = |name: &str, symbol: &str, sector: Sector, market: &Market| {
+            Company::new(name.to_string(), symbol.to_string(), sector, market.clone())
+        };
+        let hdfc = company("HDFC Bank", "HDFCBANK", Sector::Banking, &nse);
+        let icici = company("ICICI Bank", "ICICIBANK", Sector::Banking, &nse);
+        let sbi = company("State Bank of India", "SBIN", Sector::Banking, &bse);
+        let infosys = company("Infosys", "INFY", Sector::Technology, &nse);
+
+        let hdfc_id = engine
+            .list_instrument(Instrument::new(hdfc.clone()).with_isin("INE040A01034".to_string()))
+            .unwrap();
+        let icici_id = engine.list_new_company(icici.clone()).unwrap();
+        engine.list_new_company(sbi).unwrap();
+        engine.list_new_company(infosys).unwrap();
+        assert_eq!(hdfc_id, InstrumentId(1));
+        assert_eq!(icici_id, InstrumentId(2));
+
+        let instrument = engine.instrument(&hdfc).unwrap();
+        assert_eq!(instrument.isin, Some("INE040A01034".to_string()));
+        assert_eq!(instrument.currency, "INR");
+        assert_eq!(instrument.listing_date, NSE_TRADING_HOURS);
+        assert_eq!(instrument.company.name(), "HDFC Bank");
+        assert_eq!(instrument.company.symbol(), "HDFCBANK");
+        assert_eq!(instrument.company.sector(), &Sector::Banking);
+        assert_eq!(instrument.company.market(), &nse);
+
+        let registry = engine.instruments();
+        let nse_banks: Vec<&str> = registry
+            .by_sector_and_market(&Sector::Banking, &nse)
+            .iter()
+            .map(|instrument| instrument.symbol())
+            .collect();
+        assert_eq!(nse_banks, vec!["HDFCBANK", "ICICIBANK"]);
+        assert_eq!(registry.by_sector(&Sector::Banking).len(), 3);
+        assert_eq!(registry.by_market(&nse).len(), 3);
+        assert_eq!(registry.find("SBIN", None), Ok(InstrumentId(3)));
+        assert!(engine.get_orderbook(icici_id).is_some());
+
+        // Delisted instruments keep their reference data but leave every query.
+        engine.delist_company(&icici).unwrap();
+        let registry = engine.instruments();
+        assert_eq!(
+            registry.get(icici_id).unwrap().status,
+            InstrumentStatus::Delisted
+        );
+        assert_eq!(
+            registry.by_sector_and_market(&Sector::Banking, &nse).len(),
+            1
+        );
+        assert!(engine.get_orderbook(icici_id).is_none());
+        assert_eq!(engine.list_new_company(icici).unwrap(), InstrumentId(5));
+    }
+
+    #[test]
+    fn test_depth_snapshot() {
+        let mut order_book = OrderBook::new();
+        assert_eq!(order_book.depth(5).spread(), None);
+
+        for (quantity, price) in [(10, 100), (5, 100), (7, 99), (3, 98)] {
+            order_book.add_order_to_orderbook(Order::new(
+                Decimal::from(quantity),
+                Decimal::from(price),
+                BuyOrSell::Buy,
+            ));
+        }
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(20), dec!(97), BuyOrSell::Buy).with_hidden(true),
+        );
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(50), dec!(101), BuyOrSell::Sell).with_display_quantity(dec!(10)),
+        );
+        order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(101), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(8), dec!(102), BuyOrSell::Sell));
+
+        let depth = order_book.depth(2);
+        assert_eq!(
+            depth.bids,
+            vec![
+                DepthLevel {
+                    price: dec!(100),
+                    quantity: dec!(15),
+                    order_count: 2
+                },
+                DepthLevel {
+                    price: dec!(99),
+                    quantity: dec!(7),
+                    order_count: 1
+                },
+            ]
+        );
+        // Only the displayed peak of the iceberg order is published.
+        assert_eq!(
+            depth.asks,
+            vec![
+                DepthLevel {
+                    price: dec!(101),
+                    quantity: dec!(15),
+                    order_count: 2
+                },
+                DepthLevel {
+                    price: dec!(102),
+                    quantity: dec!(8),
+                    order_count: 1
+                },
+            ]
+        );
+        assert_eq!(depth.spread(), Some(dec!(1)));
+        assert_eq!(depth.mid_price(), Some(dec!(100.5)));
+
+        // Levels with hidden orders only are left out.
+        assert_eq!(order_book.depth(10).bids.len(), 3);
+    }
+
+    #[test]
+    fn test_market_by_order_feed() {
+        let mut order_book = OrderBook::new();
+        let first_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(700), BuyOrSell::Sell));
+        let updates = order_book.drain_book_updates();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].sequence, 1);
+        assert_eq!(
+            updates[0].event,
+            BookEvent::OrderAdded {
+                id: first_id,
+                side: BuyOrSell::Sell,
+                price: dec!(700),
+                quantity: dec!(10),
+            }
+        );
+
+        // A consumer starts from a snapshot and keeps it up to date with the deltas.
+        let mut rebuilt = order_book.market_by_order();
+        let mut check = |order_book: &mut OrderBook| {
+            for update in order_book.drain_book_updates() {
+                rebuilt.apply(&update);
+            }
+            assert_eq!(rebuilt, order_book.market_by_order());
+        };
+
+        let iceberg_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(30), dec!(701), BuyOrSell::Sell).with_display_quantity(dec!(10)),
+        );
+        order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(701), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(50), dec!(702), BuyOrSell::Sell).with_hidden(true),
+        );
+        let buy_id =
+            order_book.add_order_to_orderbook(Order::new(dec!(20), dec!(699), BuyOrSell::Buy));
+        check(&mut order_book);
+
+        // Executions, including an iceberg peak being replenished.
+        order_book.match_market_order(&mut Order::market(dec!(25), BuyOrSell::Buy));
+        check(&mut order_book);
+        order_book.modify_quantity(buy_id, dec!(15)).unwrap();
+        order_book.modify_quantity(iceberg_id, dec!(25)).unwrap();
+        check(&mut order_book);
+        order_book
+            .replace_order(buy_id, dec!(701), dec!(12))
+            .unwrap();
+        check(&mut order_book);
+
+        // Self trade prevention shrinks the resting order in place.
+        order_book.set_self_trade_prevention(SelfTradePrevention::DecrementAndCancel);
+        let own_id = order_book.add_order_to_orderbook(
+            Order::new(dec!(30), dec!(698), BuyOrSell::Buy).with_account_id("ACC-1".to_string()),
+        );
+        order_book.match_limit_order(
+            &mut Order::new(dec!(10), dec!(698), BuyOrSell::Sell)
+                .with_account_id("ACC-1".to_string()),
+        );
+        check(&mut order_book);
+        order_book.cancel_order(own_id).unwrap();
+        order_book.match_market_order(&mut Order::market(dec!(100), BuyOrSell::Buy));
+        check(&mut order_book);
+
+        // Hidden icebergs stay out of the feed when they replenish.
+        order_book.add_order_to_orderbook(
+            Order::new(dec!(20), dec!(703), BuyOrSell::Sell)
+                .with_hidden(true)
+                .with_display_quantity(dec!(5)),
+        );
+        order_book.match_market_order(&mut Order::market(dec!(5), BuyOrSell::Buy));
+        assert!(order_book.drain_book_updates().is_empty());
+        order_book.match_market_order(&mut Order::market(dec!(15), BuyOrSell::Buy));
+        check(&mut order_book);
+
+        // Auction uncrosses execute resting orders on both sides.
+        order_book.start_auction();
+        order_book.match_limit_order(&mut Order::new(dec!(8), dec!(710), BuyOrSell::Buy));
+        order_book.match_limit_order(&mut Order::new(dec!(5), dec!(709), BuyOrSell::Sell));
+        order_book.uncross_auction();
+        check(&mut order_book);
+
+        let updates = order_book.drain_book_updates();
+        assert!(updates.is_empty());
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(705), BuyOrSell::Sell));
+        order_book.cancel_all_orders();
+        let updates = order_book.drain_book_updates();
+        assert!(matches!(
+            updates.last().unwrap().event,
+            BookEvent::LevelCleared { side: BuyOrSell::Sell, price } if price == dec!(705)
+        ));
+        assert_eq!(order_book.market_by_order().asks.len(), 0);
+    }
+
+    #[test]
+    fn test_ticker() {
+        let mut engine = MatchingEngine::new();
+        let company = |name: &str, symbol: &str| {
+            Company::new(
+                name.to_string(),
+                symbol.to_string(),
+                Sector::Finance,
+                Market::CryptoMarket(CryptoExchange::Binance),
+            )
+        };
+        let bitcoin = company("Bitcoin", "BTC");
+        engine.list_new_company(bitcoin.clone()).unwrap();
+        engine.list_new_company(company("Ethereum", "ETH")).unwrap();
+
+        let order_book = engine.get_company_orderbook(&bitcoin).unwrap();
+        order_book.set_previous_close(dec!(100));
+        order_book.add_order_to_orderbook(Order::new(dec!(10), dec!(101), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(103), BuyOrSell::Sell));
+        order_book.add_order_to_orderbook(Order::new(dec!(5), dec!(97), BuyOrSell::Buy));
+        order_book.match_limit_order(&mut Order::new(dec!(4), dec!(101), BuyOrSell::Buy));
+        order_book.match_market_order(&mut Order::market(dec!(8), BuyOrSell::Buy));
+        order_book.match_market_order(&mut Order::market(dec!(2), BuyOrSell::Sell));
+
+        let ticker = engine.ticker(&bitcoin).unwrap();
+        assert_eq!(
+            ticker.best_bid,
+            Some(DepthLevel {
+                price: dec!(97),
+                quantity: dec!(3),
+                order_count: 1
+            })
+        );
+        assert_eq!(
+            ticker.best_ask,
+            Some(DepthLevel {
+                price: dec!(103),
+                quantity: dec!(3),
+                order_count: 1
+            })
+        );
+        assert_eq!(ticker.last_trade_price, Some(dec!(97)));
+        assert_eq!(ticker.last_trade_quantity, Some(dec!(2)));
+        let statistics = ticker.statistics;
+        assert_eq!(statistics.open, Some(dec!(101)));
+        assert_eq!(statistics.high, Some(dec!(103)));
+        assert_eq!(statistics.low, Some(dec!(97)));
+        assert_eq!(statistics.close, Some(dec!(97)));
+        assert_eq!(statistics.volume, dec!(14));
+        assert_eq!(statistics.turnover, dec!(1410));
+        assert_eq!(statistics.trade_count, 4);
+        assert_eq!(ticker.change(), Some(dec!(-3)));
+        assert_eq!(ticker.change_percentage(), Some(dec!(-3)));
+
+        let tickers = engine.tickers();
+        assert_eq!(tickers.len(), 2);
+        assert_eq!(tickers[0].0, bitcoin);
+        assert_eq!(tickers[1].1.last_trade_price, None);
+
+        // The next trading day starts from the close of this one.
+        let order_book = engine.get_company_orderbook(&bitcoin).unwrap();
+        order_book.set_session(TradingSession::PostClose);
+        order_book.set_session(TradingSession::PreOpen);
+        let ticker = order_book.ticker();
+        assert_eq!(ticker.previous_close, Some(dec!(97)));
+        assert_eq!(ticker.statistics.volume, dec!(0));
+        assert_eq!(ticker.change(), Some(dec!(0)));
+    }
+
+    #[test]
+    fn test_candles() {
+        let start = NSE_TRADING_HOURS;
+        let clock = Arc::new(ManualClock::new(start));
+        let mut engine = MatchingEngine::with_clock(clock.clone());
+        let company = Company::new(
+            "Bitcoin".to_string(),
+            "BTC".to_string(),
+            Sector::Finance,
+            Market::CryptoMarket(CryptoExchange::Binance),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        assert!(engine
+            .candles(&company, CandleInterval::OneMinute)
+            .unwrap()
+            .current()
+            .is_none());
+
+        let trade_at =
+            |engine: &mut MatchingEngine, offset: u64, price: Decimal, quantity: Decimal| {
+                clock.set(start + offset);
+                let order_book = engine.get_company_orderbook(&company).unwrap();
+                order_book.add_order_to_orderbook(Order::new(quantity, price, BuyOrSell::Sell));
+                order_book.match_market_order(&mut Order::market(quantity, BuyOrSell::Buy));
+            };
+        trade_at(&mut engine, 0, dec!(100), dec!(2));
+        trade_at(&mut engine, 10_000, dec!(102), dec!(1));
+        trade_at(&mut engine, 30_000, dec!(99), dec!(3));
+        trade_at(&mut engine, 3 * MINUTE + 5_000, dec!(101), dec!(1));
+
+        // Minutes without trades are filled with flat bars at the last close.
+        let minutes = engine.candles(&company, CandleInterval::OneMinute).unwrap();
+        assert_eq!(
+            minutes.history(),
+            &[
+                Candle {
+                    start,
+                    open: dec!(100),
+                    high: dec!(102),
+                    low: dec!(99),
+                    close: dec!(99),
+                    volume: dec!(6),
+                    trade_count: 3
+                },
+                Candle::flat(start + MINUTE, dec!(99)),
+                Candle::flat(start + 2 * MINUTE, dec!(99)),
+            ]
+        );
+        let current = minutes.current().unwrap();
+        assert_eq!(current.start, start + 3 * MINUTE);
+        assert_eq!(current.open, dec!(101));
+        assert_eq!(current.volume, dec!(1));
+
+        let five_minutes = engine
+            .candles(&company, CandleInterval::FiveMinutes)
+            .unwrap();
+        assert!(five_minutes.history().is_empty());
+        let current = five_minutes.current().unwrap();
+        assert_eq!(
+            (current.open, current.high, current.low, current.close),
+            (dec!(100), dec!(102), dec!(99), dec!(101))
+        );
+        assert_eq!(current.volume, dec!(7));
+        assert_eq!(current.trade_count, 4);
+        let seconds = engine.candles(&company, CandleInterval::OneSecond).unwrap();
+        assert_eq!(seconds.history().len(), 185);
+
+        // Bars are closed as time goes by, even without trades.
+        clock.set(start + 5 * MINUTE + 5_000);
+        engine.update_sessions();
+        let minutes = engine.candles(&company, CandleInterval::OneMinute).unwrap();
+        assert_eq!(minutes.history().len(), 5);
+        assert_eq!(
+            minutes.current(),
+            Some(&Candle::flat(start + 5 * MINUTE, dec!(101)))
+        );
+        let five_minutes = engine
+            .candles(&company, CandleInterval::FiveMinutes)
+            .unwrap();
+        assert_eq!(five_minutes.history()[0].volume, dec!(7));
+        let days = engine.candles(&company, CandleInterval::OneDay).unwrap();
+        assert_eq!(
+            days.current().unwrap().start,
+            CandleInterval::OneDay.bar_start(start)
+        );
+    }
+
+    #[test]
+    fn test_vwap_twap_and_turnover() {
+        let start = NSE_TRADING_HOURS;
+        let clock = Arc::new(ManualClock::new(start));
+        let mut engine = MatchingEngine::with_clock(clock.clone());
+        let company = Company::new(
+            "Ethereum".to_string(),
+            "ETH".to_string(),
+            Sector::Finance,
+            Market::CryptoMarket(CryptoExchange::Binance),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        assert_eq!(
+            engine.session_benchmarks(&company),
+            Some(Benchmarks::default())
+        );
+
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(99), BuyOrSell::Buy));
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(101), BuyOrSell::Sell));
+        // Mid-price 100 until the new best offer moves it to 99.5.
+        clock.set(start + 2 * MINUTE);
+        order_book.add_order_to_orderbook(Order::new(dec!(2), dec!(100), BuyOrSell::Sell));
+        // Back to 100 once the offer is taken.
+        clock.set(start + 3 * MINUTE);
+        order_book.match_market_order(&mut Order::market(dec!(2), BuyOrSell::Buy));
+        // No mid-price at all once the bid is taken.
+        clock.set(start + 4 * MINUTE);
+        order_book.match_market_order(&mut Order::market(dec!(1), BuyOrSell::Sell));
+        clock.set(start + 6 * MINUTE);
+
+        let session = engine.session_benchmarks(&company).unwrap();
+        assert_eq!(session.notional, dec!(299));
+        assert_eq!(session.volume, dec!(3));
+        assert_eq!(session.trade_count, 2);
+        assert_eq!(session.vwap, Some(dec!(299) / dec!(3)));
+        assert_eq!(session.twap, Some(dec!(99.875)));
+
+        let rolling = engine
+            .rolling_benchmarks(&company, 2 * MINUTE + 30_000)
+            .unwrap();
+        assert_eq!(
+            rolling,
+            Benchmarks {
+                vwap: Some(dec!(99)),
+                twap: Some(dec!(100)),
+                notional: dec!(99),
+                volume: dec!(1),
+                trade_count: 1,
+            }
+        );
+
+        // Nothing is kept beyond the retention period.
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        order_book.set_analytics_retention(MINUTE);
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(98), BuyOrSell::Buy));
+        let rolling = engine.rolling_benchmarks(&company, 10 * MINUTE).unwrap();
+        assert_eq!(rolling.trade_count, 0);
+        assert_eq!(rolling.twap, None);
+    }
+
+    #[test]
+    fn test_session_twap_stops_at_close() {
+        let start = NSE_TRADING_HOURS;
+        let clock = Arc::new(ManualClock::new(start));
+        let mut engine = MatchingEngine::with_clock(clock.clone());
+        let company = Company::new(
+            "Nactore".to_string(),
+            "NACT".to_string(),
+            Sector::Technology,
+            Market::IndianMarket(IndianExchange::NSE),
+        );
+        engine.list_new_company(company.clone()).unwrap();
+        let order_book = engine.get_company_orderbook(&company).unwrap();
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(99), BuyOrSell::Buy));
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(101), BuyOrSell::Sell));
+        // 10:30 IST : mid-price 99.5 until the close.
+        clock.set(start + 30 * MINUTE);
+        order_book.add_order_to_orderbook(Order::new(dec!(1), dec!(100), BuyOrSell::Sell));
+
+        // 15:40 IST : post close.
+        clock.set(start + 5 * 60 * MINUTE + 40 * MINUTE);
+        engine.update_sessions();
+        let twap = Some(dec!(33845) / dec!(340));
+        assert_eq!(engine.session_benchmarks(&company).unwrap().twap, twap);
+
+        // 20:00 IST : nothing accrues after the close.
+        clock.set(start + 10 * 60 * MINUTE);
+        engine.update_sessions();
+        assert_eq!(engine.session_benchmarks(&company).unwrap().twap, twap);
+
+        // 09:00 IST the next day : the new session starts from the mid-price left at the close.
+        clock.set(start + 23 * 60 * MINUTE);
+        engine.update_sessions();
+        clock.advance(15 * MINUTE);
+        assert_eq!(
+            engine.session_benchmarks(&company).unwrap().twap,
+            Some(dec!(99.5))
+        );
+    }
 }